@@ -1,8 +1,8 @@
-use async_http_codec::{BodyDecodeWithContinue, BodyEncode, RequestHead, ResponseHead};
+use async_http_codec::{decode_request, encode_response, Limits};
 use async_web_server::TcpIncoming;
 use futures::prelude::*;
-use http::header::{CONNECTION, CONTENT_LENGTH, TRANSFER_ENCODING};
-use http::{Method, Request, Response, StatusCode};
+use http::header::CONNECTION;
+use http::{Method, Response, StatusCode};
 use log::LevelFilter;
 use simple_logger::SimpleLogger;
 use smol::future::block_on;
@@ -34,14 +34,7 @@ fn main() -> anyhow::Result<()> {
 }
 
 async fn handle(mut transport: impl AsyncRead + AsyncWrite + Unpin) -> anyhow::Result<()> {
-    let (_, request_head) = RequestHead::decode(&mut transport).await.unwrap();
-
-    let mut request_body = String::new();
-    BodyDecodeWithContinue::from_head(&request_head, &mut transport)?
-        .read_to_string(&mut request_body)
-        .await?;
-
-    let request = Request::from_parts(request_head.into(), request_body);
+    let request = decode_request(&mut transport, &Limits::default()).await?;
     log::info!("received request: {:?}", &request);
 
     let mut response = Response::<&[u8]>::new(&[]);
@@ -49,28 +42,20 @@ async fn handle(mut transport: impl AsyncRead + AsyncWrite + Unpin) -> anyhow::R
         .headers_mut()
         .insert(CONNECTION, "close".try_into()?);
     match (request.method(), request.uri().path()) {
-        (&Method::GET, "/") => {
-            response
-                .headers_mut()
-                .insert(CONTENT_LENGTH, HTML.len().into());
-            *response.body_mut() = HTML;
-        }
-        (&Method::POST, _) => {
-            response
-                .headers_mut()
-                .insert(TRANSFER_ENCODING, "chunked".try_into()?);
-            *response.body_mut() = request.body().as_bytes();
-        }
+        (&Method::GET, "/") => *response.body_mut() = HTML,
+        (&Method::POST, _) => *response.body_mut() = request.body(),
         (&Method::GET, _) => *response.status_mut() = StatusCode::NOT_FOUND,
         (_, _) => *response.status_mut() = StatusCode::METHOD_NOT_ALLOWED,
     }
 
-    ResponseHead::ref_response(&response)
-        .encode(&mut transport)
-        .await?;
-    let mut body_encode = BodyEncode::from_headers(&response.headers(), transport)?;
-    body_encode.write_all(response.body()).await?;
-    body_encode.close().await?;
+    encode_response(
+        &mut transport,
+        &response,
+        request.method(),
+        request.version(),
+    )
+    .await?;
+    transport.close().await?;
     log::info!(
         "sent response with status \"{}\" on \"{}\"",
         response.status(),
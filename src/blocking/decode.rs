@@ -0,0 +1,56 @@
+use crate::blocking::block_on_poll;
+use crate::{BodyDecodeState, Drained, ResponseHead};
+use futures::io::AllowStdIo;
+use http::{HeaderMap, Method};
+use std::io;
+use std::io::Read;
+
+/// Blocking counterpart of [`crate::BodyDecode`], reading the body from a [`Read`] transport.
+pub struct BodyDecode<R: Read> {
+    transport: AllowStdIo<R>,
+    state: BodyDecodeState,
+}
+
+impl<R: Read> BodyDecode<R> {
+    pub fn new(transport: R, length: Option<u64>) -> Self {
+        Self::from_state(BodyDecodeState::new(length), transport)
+    }
+    pub fn from_headers(headers: &HeaderMap, transport: R) -> anyhow::Result<Self> {
+        Ok(Self::from_state(
+            BodyDecodeState::from_headers(headers)?,
+            transport,
+        ))
+    }
+    pub fn from_response_head(
+        head: &ResponseHead,
+        request_method: &Method,
+        transport: R,
+    ) -> anyhow::Result<Self> {
+        Ok(Self::from_state(
+            BodyDecodeState::from_response_head(head, request_method)?,
+            transport,
+        ))
+    }
+    pub fn from_state(state: BodyDecodeState, transport: R) -> Self {
+        Self {
+            transport: AllowStdIo::new(transport),
+            state,
+        }
+    }
+    pub fn into_inner(self) -> (BodyDecodeState, R) {
+        (self.state, self.transport.into_inner())
+    }
+    pub fn state(&self) -> &BodyDecodeState {
+        &self.state
+    }
+    /// Discards the rest of the body, reading at most `limit` body bytes.
+    pub fn drain(&mut self, mut limit: u64) -> io::Result<Drained> {
+        block_on_poll(|cx| self.state.poll_drain(&mut self.transport, cx, &mut limit))
+    }
+}
+
+impl<R: Read> Read for BodyDecode<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        block_on_poll(|cx| self.state.poll_read(&mut self.transport, cx, buf))
+    }
+}
@@ -0,0 +1,82 @@
+use crate::blocking::block_on_poll;
+use crate::{BodyEncodeState, RequestHead, ResponseHead};
+use futures::io::AllowStdIo;
+use http::{HeaderMap, Method, Version};
+use std::io;
+use std::io::Write;
+
+/// Blocking counterpart of [`crate::BodyEncode`], writing the body to a [`Write`] transport.
+///
+/// Chunked bodies must be completed with [`finish`](Self::finish) or [`close`](Self::close).
+pub struct BodyEncode<W: Write> {
+    transport: AllowStdIo<W>,
+    state: BodyEncodeState,
+}
+
+impl<W: Write> BodyEncode<W> {
+    pub fn new(transport: W, length: Option<u64>) -> Self {
+        Self::from_state(BodyEncodeState::new(length), transport)
+    }
+    pub fn from_headers(headers: &HeaderMap, transport: W) -> anyhow::Result<Self> {
+        Ok(Self::from_state(
+            BodyEncodeState::from_headers(headers)?,
+            transport,
+        ))
+    }
+    pub fn from_response_head(
+        head: &mut ResponseHead,
+        request_method: &Method,
+        peer: Version,
+        transport: W,
+    ) -> anyhow::Result<Self> {
+        Ok(Self::from_state(
+            BodyEncodeState::from_response_head(head, request_method, peer)?,
+            transport,
+        ))
+    }
+    pub fn for_request(
+        head: &mut RequestHead,
+        length: Option<u64>,
+        peer: Version,
+        transport: W,
+    ) -> anyhow::Result<Self> {
+        Ok(Self::from_state(
+            BodyEncodeState::for_request(head, length, peer)?,
+            transport,
+        ))
+    }
+    pub fn for_response(
+        head: &mut ResponseHead,
+        length: Option<u64>,
+        peer: Version,
+        transport: W,
+    ) -> Self {
+        Self::from_state(BodyEncodeState::for_response(head, length, peer), transport)
+    }
+    pub fn from_state(state: BodyEncodeState, transport: W) -> Self {
+        Self {
+            transport: AllowStdIo::new(transport),
+            state,
+        }
+    }
+    pub fn checkpoint(self) -> (W, BodyEncodeState) {
+        (self.transport.into_inner(), self.state)
+    }
+    /// Completes the body without closing the transport, see [`BodyEncodeState::poll_finish`].
+    pub fn finish(&mut self) -> io::Result<()> {
+        block_on_poll(|cx| self.state.poll_finish(&mut self.transport, cx))
+    }
+    /// Completes the body and flushes the transport.
+    pub fn close(&mut self) -> io::Result<()> {
+        block_on_poll(|cx| self.state.poll_close(&mut self.transport, cx))
+    }
+}
+
+impl<W: Write> Write for BodyEncode<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        block_on_poll(|cx| self.state.poll_write(&mut self.transport, cx, buf))
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        block_on_poll(|cx| self.state.poll_flush(&mut self.transport, cx))
+    }
+}
@@ -0,0 +1,75 @@
+mod decode;
+mod encode;
+
+pub use decode::*;
+pub use encode::*;
+
+use futures::task::noop_waker_ref;
+use std::io;
+use std::task::{Context, Poll};
+
+/// Drives a poll function over a blocking transport, which never returns [`Poll::Pending`].
+fn block_on_poll<T>(
+    mut poll: impl FnMut(&mut Context<'_>) -> Poll<io::Result<T>>,
+) -> io::Result<T> {
+    match poll(&mut Context::from_waker(noop_waker_ref())) {
+        Poll::Ready(result) => result,
+        Poll::Pending => Err(io::ErrorKind::WouldBlock.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::blocking::{BodyDecode, BodyEncode};
+    use crate::{Drained, ResponseHead};
+    use http::{HeaderMap, Method, StatusCode, Version};
+    use std::borrow::Cow;
+    use std::io::{Read, Write};
+
+    const CHUNKED: &[u8] = b"6\r\nhello \r\n6\r\nworld!\r\n0\r\n\r\n";
+
+    #[test]
+    fn body_round_trip() {
+        let mut encode = BodyEncode::new(Vec::new(), None);
+        encode.write_all(b"hello ").unwrap();
+        encode.flush().unwrap();
+        encode.write_all(b"world!").unwrap();
+        encode.finish().unwrap();
+        let (output, _) = encode.checkpoint();
+        assert_eq!(output, CHUNKED);
+
+        let input = [CHUNKED, b"next"].concat();
+        let mut decode = BodyDecode::new(&input[..], None);
+        let mut body = String::new();
+        decode.read_to_string(&mut body).unwrap();
+        assert_eq!(body, "hello world!");
+        let (state, rest) = decode.into_inner();
+        assert!(state.is_done());
+        assert_eq!(rest, b"next");
+
+        let mut decode = BodyDecode::new(&b"hello world!"[..], Some(12));
+        assert_eq!(decode.drain(6).unwrap(), Drained::MustClose);
+        assert_eq!(decode.drain(u64::MAX).unwrap(), Drained::Complete);
+        let mut encode = BodyEncode::new(Vec::new(), Some(5));
+        assert!(encode.write_all(b"hello world!").is_err());
+    }
+
+    #[test]
+    fn response_to_head() {
+        let mut headers = HeaderMap::new();
+        headers.insert("content-length", "5".parse().unwrap());
+        let mut head = ResponseHead::new(StatusCode::OK, Version::HTTP_11, Cow::Owned(headers));
+        let mut encode =
+            BodyEncode::from_response_head(&mut head, &Method::HEAD, Version::HTTP_11, Vec::new())
+                .unwrap();
+        assert_eq!(head.headers()["content-length"], "5");
+        assert!(encode.write_all(b"hello").is_err());
+
+        let mut encode =
+            BodyEncode::from_response_head(&mut head, &Method::GET, Version::HTTP_11, Vec::new())
+                .unwrap();
+        encode.write_all(b"hello").unwrap();
+        encode.finish().unwrap();
+        assert_eq!(encode.checkpoint().0, b"hello");
+    }
+}
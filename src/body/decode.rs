@@ -1,6 +1,11 @@
-use crate::body::common::length_from_headers;
+use crate::{BodyDecoder, BodyEvent, ResponseHead};
+use bytes::{Bytes, BytesMut};
 use futures::prelude::*;
+use futures::ready;
+use http::{HeaderMap, Method};
 use std::borrow::BorrowMut;
+use std::cmp::min;
+use std::future::Future;
 use std::io;
 use std::pin::Pin;
 use std::task::{Context, Poll};
@@ -8,6 +13,9 @@ use std::task::{Context, Poll};
 pub struct BodyDecode<T: BorrowMut<BodyDecodeState> + Unpin, IO: AsyncRead + Unpin> {
     transport: IO,
     state: T,
+    /// Read buffer of the [`http_body::Body`] implementation, kept across frames.
+    #[cfg(feature = "http-body")]
+    buffer: BytesMut,
 }
 
 impl<IO: AsyncRead + Unpin> BodyDecode<BodyDecodeState, IO> {
@@ -17,12 +25,58 @@ impl<IO: AsyncRead + Unpin> BodyDecode<BodyDecodeState, IO> {
     pub fn from_headers(headers: &http::header::HeaderMap, transport: IO) -> anyhow::Result<Self> {
         Ok(BodyDecodeState::from_headers(headers)?.into_async_read(transport))
     }
+    pub fn from_response_head(
+        head: &ResponseHead,
+        request_method: &Method,
+        transport: IO,
+    ) -> anyhow::Result<Self> {
+        Ok(BodyDecodeState::from_response_head(head, request_method)?.into_async_read(transport))
+    }
 }
 
 impl<T: BorrowMut<BodyDecodeState> + Unpin, IO: AsyncRead + Unpin> BodyDecode<T, IO> {
     pub fn into_inner(self) -> (T, IO) {
         (self.state, self.transport)
     }
+    pub fn state(&self) -> &BodyDecodeState {
+        self.state.borrow()
+    }
+    #[cfg(feature = "http-body")]
+    pub(crate) fn state_mut(&mut self) -> &mut BodyDecodeState {
+        self.state.borrow_mut()
+    }
+    #[cfg(feature = "http-body")]
+    pub(crate) fn buffer_mut(&mut self) -> &mut BytesMut {
+        &mut self.buffer
+    }
+    /// Returns the transport if the body has been consumed completely.
+    pub fn into_transport(self) -> Result<IO, Self> {
+        match self.state.borrow().is_done() {
+            true => Ok(self.transport),
+            false => Err(self),
+        }
+    }
+    /// Stream of body parts of up to `chunk_size` bytes each, read directly into [`Bytes`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `chunk_size` is 0.
+    pub fn into_stream(self, chunk_size: usize) -> BodyStream<T, IO> {
+        assert!(chunk_size > 0, "chunk_size must be non-zero");
+        BodyStream {
+            decode: self,
+            buffer: BytesMut::new(),
+            chunk_size,
+        }
+    }
+    /// Discards the rest of the body, reading at most `limit` body bytes.
+    pub fn drain(&mut self, mut limit: u64) -> impl Future<Output = io::Result<Drained>> + '_ {
+        future::poll_fn(move |cx| {
+            self.state
+                .borrow_mut()
+                .poll_drain(&mut self.transport, cx, &mut limit)
+        })
+    }
 }
 
 impl<T: BorrowMut<BodyDecodeState> + Unpin, IO: AsyncRead + Unpin> AsyncRead for BodyDecode<T, IO> {
@@ -38,55 +92,95 @@ impl<T: BorrowMut<BodyDecodeState> + Unpin, IO: AsyncRead + Unpin> AsyncRead for
     }
 }
 
-pub struct BodyDecodeState {
-    parser_state: Parser,
-    _compression_state: (),
-    remaining: u64,
+pub struct BodyStream<T: BorrowMut<BodyDecodeState> + Unpin, IO: AsyncRead + Unpin> {
+    decode: BodyDecode<T, IO>,
+    buffer: BytesMut,
+    chunk_size: usize,
 }
 
-#[derive(Copy, Clone, Debug, Eq, PartialEq)]
-enum Parser {
-    FixedLength,
-    Chunked(ChunkState),
-    Failed,
-    Done,
+impl<T: BorrowMut<BodyDecodeState> + Unpin, IO: AsyncRead + Unpin> BodyStream<T, IO> {
+    pub fn into_inner(self) -> BodyDecode<T, IO> {
+        self.decode
+    }
 }
 
+impl<T: BorrowMut<BodyDecodeState> + Unpin, IO: AsyncRead + Unpin> Stream for BodyStream<T, IO> {
+    type Item = io::Result<Bytes>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        let read = poll_read_bytes(&mut this.decode, cx, &mut this.buffer, this.chunk_size);
+        match ready!(read) {
+            Ok(bytes) if bytes.is_empty() => Poll::Ready(None),
+            result => Poll::Ready(Some(result)),
+        }
+    }
+}
+
+/// Reads up to `chunk_size` bytes into `buffer` and splits them off, an empty result marks the
+/// end of input. The unfilled part of `buffer` stays initialized for the next read, so only
+/// the space taken by previous results has to be zeroed again.
+pub(crate) fn poll_read_bytes<R: AsyncRead + Unpin>(
+    reader: &mut R,
+    cx: &mut Context<'_>,
+    buffer: &mut BytesMut,
+    chunk_size: usize,
+) -> Poll<io::Result<Bytes>> {
+    buffer.resize(chunk_size, 0);
+    let n = ready!(Pin::new(reader).poll_read(cx, buffer))?;
+    Poll::Ready(Ok(buffer.split_to(n).freeze()))
+}
+
+/// Outcome of draining a body.
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
-enum ChunkState {
-    Size,
-    SizeLF,
-    Content,
-    ContentCR,
-    ContentLF,
-    EndCR,
-    EndLF,
+pub enum Drained {
+    /// The body was consumed completely and the connection can carry the next message.
+    Complete,
+    /// The rest of the body could not be consumed within the limit, the connection must close.
+    MustClose,
 }
 
-fn err_kind<T>(kind: io::ErrorKind) -> Poll<io::Result<T>> {
-    Poll::Ready(Err(kind.into()))
+pub struct BodyDecodeState {
+    decoder: BodyDecoder,
+    _compression_state: (),
+    trailers: Option<HeaderMap>,
 }
 
 impl BodyDecodeState {
-    pub fn from_headers(headers: &http::header::HeaderMap) -> anyhow::Result<Self> {
-        Ok(Self::new(length_from_headers(headers)?))
+    pub fn from_headers(headers: &HeaderMap) -> anyhow::Result<Self> {
+        Ok(Self::from_decoder(BodyDecoder::from_headers(headers)?))
+    }
+    /// Body decoder for a response, following the message length rules of RFC 9112 section 6.3.
+    /// Responses without framing headers are delimited by the connection closing.
+    pub fn from_response_head(
+        head: &ResponseHead,
+        request_method: &Method,
+    ) -> anyhow::Result<Self> {
+        Ok(Self::from_decoder(BodyDecoder::from_response_head(
+            head,
+            request_method,
+        )?))
+    }
+    /// Body decoder for a body delimited by the connection closing.
+    pub fn until_close() -> Self {
+        Self::from_decoder(BodyDecoder::until_close())
     }
     pub fn new(length: Option<u64>) -> Self {
-        let (parser_state, remaining) = match length {
-            Some(0) => (Parser::Done, 0),
-            Some(length) => (Parser::FixedLength, length),
-            None => (Parser::Chunked(ChunkState::Size), 0),
-        };
+        Self::from_decoder(BodyDecoder::new(length))
+    }
+    pub fn from_decoder(decoder: BodyDecoder) -> Self {
         Self {
-            parser_state,
+            decoder,
             _compression_state: (),
-            remaining,
+            trailers: None,
         }
     }
     pub fn into_async_read<IO: AsyncRead + Unpin>(self, transport: IO) -> BodyDecode<Self, IO> {
         BodyDecode {
             transport,
             state: self,
+            #[cfg(feature = "http-body")]
+            buffer: BytesMut::new(),
         }
     }
     pub fn as_async_read<IO: AsyncRead + Unpin>(
@@ -96,6 +190,49 @@ impl BodyDecodeState {
         BodyDecode {
             transport,
             state: self,
+            #[cfg(feature = "http-body")]
+            buffer: BytesMut::new(),
+        }
+    }
+    pub fn is_done(&self) -> bool {
+        self.decoder.is_done()
+    }
+    /// Whether the body ends when the transport is closed.
+    pub fn is_close_delimited(&self) -> bool {
+        self.decoder.is_close_delimited()
+    }
+    /// Number of body bytes left to read, if known upfront.
+    pub fn remaining(&self) -> Option<u64> {
+        self.decoder.remaining()
+    }
+    /// Trailer fields, once a chunked body carrying them was read completely.
+    pub fn trailers(&self) -> Option<&HeaderMap> {
+        self.trailers.as_ref()
+    }
+    pub fn take_trailers(&mut self) -> Option<HeaderMap> {
+        self.trailers.take()
+    }
+    pub fn poll_drain<IO: AsyncRead + Unpin>(
+        &mut self,
+        transport: &mut IO,
+        cx: &mut Context<'_>,
+        limit: &mut u64,
+    ) -> Poll<io::Result<Drained>> {
+        let mut buf = [0u8; 1024];
+        loop {
+            if matches!(self.remaining(), Some(remaining) if remaining > *limit) {
+                return Poll::Ready(Ok(Drained::MustClose));
+            }
+            let max_read_size = min(buf.len() as u64, limit.saturating_add(1)) as usize;
+            match self.poll_read(transport, cx, &mut buf[0..max_read_size]) {
+                Poll::Ready(Ok(0)) => return Poll::Ready(Ok(Drained::Complete)),
+                Poll::Ready(Ok(n)) if n as u64 > *limit => {
+                    return Poll::Ready(Ok(Drained::MustClose))
+                }
+                Poll::Ready(Ok(n)) => *limit -= n as u64,
+                Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+                Poll::Pending => return Poll::Pending,
+            }
         }
     }
     pub fn poll_read<IO: AsyncRead + Unpin>(
@@ -105,86 +242,33 @@ impl BodyDecodeState {
         buf: &mut [u8],
     ) -> Poll<io::Result<usize>> {
         loop {
-            let max_read_size = match self.parser_state {
-                Parser::Failed => return err_kind(io::ErrorKind::BrokenPipe),
-                Parser::Done => return Poll::Ready(Ok(0)),
-                Parser::FixedLength | Parser::Chunked(ChunkState::Content) => {
-                    if buf.len() as u64 > self.remaining {
-                        self.remaining as usize
-                    } else {
-                        buf.len()
-                    }
-                }
-                Parser::Chunked(chunked_state) => {
-                    let mut next = [0u8];
-                    match Pin::new(&mut *transport).poll_read(cx, &mut next) {
-                        Poll::Ready(Err(err)) => {
-                            self.parser_state = Parser::Failed;
-                            return Poll::Ready(Err(err));
-                        }
-                        Poll::Pending => return Poll::Pending,
-                        Poll::Ready(Ok(0)) => {
-                            self.parser_state = Parser::Failed;
-                            return err_kind(io::ErrorKind::UnexpectedEof);
-                        }
-                        Poll::Ready(Ok(_)) => {
-                            self.parser_state = match (chunked_state, next[0]) {
-                                (ChunkState::Size, b'\r') => Parser::Chunked(ChunkState::SizeLF),
-                                (ChunkState::Size, hex_digit) => {
-                                    self.remaining *= 16;
-                                    self.remaining += match hex_digit {
-                                        b'0'..=b'9' => 0 + hex_digit - b'0',
-                                        b'a'..=b'f' => 10 + hex_digit - b'a',
-                                        b'A'..=b'F' => 10 + hex_digit - b'A',
-                                        _ => {
-                                            self.parser_state = Parser::Failed;
-                                            return err_kind(io::ErrorKind::InvalidData);
-                                        }
-                                    } as u64;
-                                    Parser::Chunked(ChunkState::Size)
-                                }
-                                (ChunkState::SizeLF, b'\n') => match self.remaining {
-                                    0 => Parser::Chunked(ChunkState::EndCR),
-                                    _ => Parser::Chunked(ChunkState::Content),
-                                },
-                                (ChunkState::Content, _) => unreachable!(),
-                                (ChunkState::ContentCR, b'\r') => {
-                                    Parser::Chunked(ChunkState::ContentLF)
-                                }
-                                (ChunkState::ContentLF, b'\n') => Parser::Chunked(ChunkState::Size),
-                                (ChunkState::EndCR, b'\r') => Parser::Chunked(ChunkState::EndLF),
-                                (ChunkState::EndLF, b'\n') => Parser::Done,
-                                (_, _) => return err_kind(io::ErrorKind::InvalidData),
-                            }
-                        }
-                    }
-                    continue;
-                }
-            };
-            return match Pin::new(&mut *transport).poll_read(cx, &mut buf[0..max_read_size]) {
-                Poll::Ready(Err(err)) => {
-                    self.parser_state = Parser::Failed;
-                    Poll::Ready(Err(err))
-                }
+            let max_read_size = min(self.decoder.read_hint(), buf.len());
+            if max_read_size == 0 {
+                return match self.decoder.push(&[])? {
+                    (_, Some(BodyEvent::End)) | (_, None) => Poll::Ready(Ok(0)),
+                    _ => unreachable!(),
+                };
+            }
+            // Framing bytes are read one at a time, so data always starts at the front of `buf`.
+            let n = match Pin::new(&mut *transport).poll_read(cx, &mut buf[0..max_read_size]) {
                 Poll::Ready(Ok(0)) => {
-                    self.parser_state = Parser::Failed;
-                    err_kind(io::ErrorKind::UnexpectedEof)
+                    self.decoder.finish()?;
+                    return Poll::Ready(Ok(0));
                 }
-                Poll::Ready(Ok(n)) => {
-                    self.remaining -= n as u64;
-                    if self.remaining == 0 {
-                        self.parser_state = match self.parser_state {
-                            Parser::FixedLength => Parser::Done,
-                            Parser::Chunked(ChunkState::Content) => {
-                                Parser::Chunked(ChunkState::ContentCR)
-                            }
-                            _ => unreachable!(),
-                        }
-                    }
-                    Poll::Ready(Ok(n))
+                Poll::Ready(Ok(n)) => n,
+                Poll::Ready(Err(err)) => {
+                    self.decoder.fail();
+                    return Poll::Ready(Err(err));
                 }
-                Poll::Pending => Poll::Pending,
+                Poll::Pending => return Poll::Pending,
             };
+            match self.decoder.push(&buf[0..n])? {
+                (_, Some(BodyEvent::Data(data))) => return Poll::Ready(Ok(data.len())),
+                (_, Some(BodyEvent::Trailers(trailers))) => {
+                    self.trailers = Some(trailers.to_headers()?)
+                }
+                (_, Some(BodyEvent::End)) | (_, None) => {}
+            }
         }
     }
 }
@@ -1,6 +1,14 @@
 use crate::body::common::length_from_headers;
+use crate::internal::buffer_write::BufferWriteState;
+use crate::internal::enc_helpers::header_encode;
+use crate::internal::io_future::IoFutureState;
+use crate::sans_io::size_digits;
+use crate::{ChunkedEncoder, Framing, RequestHead, ResponseHead};
+use bytes::{Buf, Bytes};
 use futures::prelude::*;
-use std::cmp::min;
+use futures::ready;
+use http::{HeaderMap, Method, Version};
+use std::future::Future;
 use std::io;
 use std::pin::Pin;
 use std::task::{Context, Poll};
@@ -17,9 +25,62 @@ impl<IO: AsyncWrite + Unpin> BodyEncode<IO> {
     pub fn checkpoint(self) -> (IO, BodyEncodeState) {
         (self.transport, self.state)
     }
+    /// [`Sink`] adapter writing each item as a separate chunk if the body is chunked.
+    pub fn into_sink(self) -> BodySink<IO> {
+        BodySink {
+            encode: self,
+            frame: None,
+        }
+    }
+    /// Completes the body without closing the transport, see [`BodyEncodeState::poll_finish`].
+    pub fn finish(&mut self) -> impl Future<Output = io::Result<()>> + '_ {
+        future::poll_fn(move |cx| self.state.poll_finish(&mut self.transport, cx))
+    }
+    /// Like [`finish`](Self::finish), ending a chunked body with a trailer section. Other
+    /// framings can't carry trailers, so they are dropped.
+    pub fn finish_with_trailers<'a>(
+        &'a mut self,
+        trailers: &'a HeaderMap,
+    ) -> impl Future<Output = io::Result<()>> + 'a {
+        let mut last_chunk = None;
+        future::poll_fn(move |cx| {
+            self.state
+                .poll_finish_with_trailers(&mut self.transport, cx, trailers, &mut last_chunk)
+        })
+    }
     pub fn from_headers(headers: &http::header::HeaderMap, transport: IO) -> anyhow::Result<Self> {
         Ok(BodyEncodeState::from_headers(headers)?.into_async_write(transport))
     }
+    pub fn from_response_head(
+        head: &mut ResponseHead,
+        request_method: &Method,
+        peer: Version,
+        transport: IO,
+    ) -> anyhow::Result<Self> {
+        Ok(
+            BodyEncodeState::from_response_head(head, request_method, peer)?
+                .into_async_write(transport),
+        )
+    }
+    pub fn from_framing(framing: Framing, transport: IO) -> Self {
+        BodyEncodeState::from_framing(framing).into_async_write(transport)
+    }
+    pub fn for_request(
+        head: &mut RequestHead,
+        length: Option<u64>,
+        peer: Version,
+        transport: IO,
+    ) -> anyhow::Result<Self> {
+        Ok(BodyEncodeState::for_request(head, length, peer)?.into_async_write(transport))
+    }
+    pub fn for_response(
+        head: &mut ResponseHead,
+        length: Option<u64>,
+        peer: Version,
+        transport: IO,
+    ) -> Self {
+        BodyEncodeState::for_response(head, length, peer).into_async_write(transport)
+    }
 }
 
 impl<IO: AsyncWrite + Unpin> AsyncWrite for BodyEncode<IO> {
@@ -43,12 +104,59 @@ impl<IO: AsyncWrite + Unpin> AsyncWrite for BodyEncode<IO> {
     }
 }
 
+pub struct BodySink<IO: AsyncWrite + Unpin> {
+    encode: BodyEncode<IO>,
+    frame: Option<Frame>,
+}
+
+impl<IO: AsyncWrite + Unpin> BodySink<IO> {
+    pub fn into_inner(self) -> BodyEncode<IO> {
+        self.encode
+    }
+    fn poll_frame(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let encode = &mut self.encode;
+        encode
+            .state
+            .poll_frame(&mut self.frame, &mut encode.transport, cx)
+    }
+}
+
+impl<IO: AsyncWrite + Unpin> Sink<Bytes> for BodySink<IO> {
+    type Error = io::Error;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.get_mut().poll_frame(cx)
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: Bytes) -> io::Result<()> {
+        let this = self.get_mut();
+        if !item.is_empty() {
+            this.frame = Some(this.encode.state.start_frame(item)?);
+        }
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_frame(cx))?;
+        Pin::new(&mut this.encode).poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_frame(cx))?;
+        Pin::new(&mut this.encode).poll_close(cx)
+    }
+}
+
+#[allow(clippy::large_enum_variant)]
 pub enum BodyEncodeState {
     Fixed {
         _compression_state: (),
         remaining: u64,
     },
     Chunked(Chunked),
+    CloseDelimited,
     Failed,
     Closed,
 }
@@ -61,13 +169,56 @@ impl BodyEncodeState {
     pub fn from_headers(headers: &http::header::HeaderMap) -> anyhow::Result<Self> {
         Ok(Self::new(length_from_headers(headers)?))
     }
+    /// Selects the framing for the response to a `request_method` request from the framing
+    /// headers of `head`, like [`Framing::for_response`], and adjusts the headers to match.
+    /// Responses to `HEAD` and successful responses to `CONNECT` have no body, the headers of
+    /// a `HEAD` response are left as they are.
+    pub fn from_response_head(
+        head: &mut ResponseHead,
+        request_method: &Method,
+        peer: Version,
+    ) -> anyhow::Result<Self> {
+        length_from_headers(head.headers())?;
+        if request_method == Method::HEAD {
+            return Ok(Self::from_framing(Framing::NoBody));
+        }
+        let framing = match request_method == Method::CONNECT && head.status().is_success() {
+            true => Framing::NoBody,
+            false => Framing::for_response(head, None, peer),
+        };
+        framing.apply(head.headers_mut());
+        Ok(Self::from_framing(framing))
+    }
+    pub fn from_framing(framing: Framing) -> Self {
+        match framing {
+            Framing::NoBody => Self::new(Some(0)),
+            Framing::Length(length) => Self::new(Some(length)),
+            Framing::Chunked => Self::new(None),
+            Framing::CloseDelimited => Self::CloseDelimited,
+        }
+    }
+    /// Selects the framing for the request body, rewrites the framing headers of `head`
+    /// accordingly and returns the matching encoder state.
+    pub fn for_request(
+        head: &mut RequestHead,
+        length: Option<u64>,
+        peer: Version,
+    ) -> anyhow::Result<Self> {
+        let framing = Framing::for_request(head, length, peer)?;
+        framing.apply(head.headers_mut());
+        Ok(Self::from_framing(framing))
+    }
+    /// Selects the framing for the response body, rewrites the framing headers of `head`
+    /// accordingly and returns the matching encoder state.
+    pub fn for_response(head: &mut ResponseHead, length: Option<u64>, peer: Version) -> Self {
+        let framing = Framing::for_response(head, length, peer);
+        framing.apply(head.headers_mut());
+        Self::from_framing(framing)
+    }
     pub fn new(length: Option<u64>) -> Self {
         match length {
             None => Self::Chunked(Chunked {
-                buffer: [0u8; 1300],
-                buffered: 0,
-                written: None,
-                closing: false,
+                encoder: ChunkedEncoder::new(),
             }),
             Some(remaining) => Self::Fixed {
                 _compression_state: (),
@@ -88,18 +239,16 @@ impl BodyEncodeState {
         buf: &[u8],
     ) -> Poll<io::Result<usize>> {
         match self {
-            BodyEncodeState::Fixed { remaining: 0, .. } => {
-                return match buf.len() {
-                    0 => Poll::Ready(Ok(0)),
-                    _ => err_kind(io::ErrorKind::InvalidData),
-                };
-            }
+            BodyEncodeState::Fixed { remaining: 0, .. } => match buf.len() {
+                0 => Poll::Ready(Ok(0)),
+                _ => err_kind(io::ErrorKind::InvalidData),
+            },
             BodyEncodeState::Fixed { remaining, .. } => {
                 let max_len = match (buf.len() as u64) < *remaining {
                     true => buf.len(),
                     false => *remaining as usize,
                 };
-                return match Pin::new(&mut transport).poll_write(cx, &buf[0..max_len]) {
+                match Pin::new(&mut transport).poll_write(cx, &buf[0..max_len]) {
                     Poll::Ready(Err(err)) => {
                         *self = BodyEncodeState::Failed;
                         Poll::Ready(Err(err))
@@ -109,7 +258,7 @@ impl BodyEncodeState {
                         Poll::Ready(Ok(n))
                     }
                     Poll::Pending => Poll::Pending,
-                };
+                }
             }
             BodyEncodeState::Chunked(chunked) => match chunked.poll_write(transport, cx, buf) {
                 Poll::Ready(Err(err)) => {
@@ -118,23 +267,32 @@ impl BodyEncodeState {
                 }
                 p => p,
             },
+            BodyEncodeState::CloseDelimited => match Pin::new(&mut transport).poll_write(cx, buf) {
+                Poll::Ready(Err(err)) => {
+                    *self = BodyEncodeState::Failed;
+                    Poll::Ready(Err(err))
+                }
+                p => p,
+            },
             BodyEncodeState::Failed => err_kind(io::ErrorKind::BrokenPipe),
             BodyEncodeState::Closed => err_kind(io::ErrorKind::BrokenPipe),
         }
     }
-    fn poll_flush<IO: AsyncWrite + Unpin>(
+    pub(crate) fn poll_flush<IO: AsyncWrite + Unpin>(
         &mut self,
         mut transport: IO,
         cx: &mut Context<'_>,
     ) -> Poll<io::Result<()>> {
         match self {
-            BodyEncodeState::Fixed { .. } => match Pin::new(&mut transport).poll_flush(cx) {
-                Poll::Ready(Err(err)) => {
-                    *self = BodyEncodeState::Failed;
-                    Poll::Ready(Err(err))
+            BodyEncodeState::Fixed { .. } | BodyEncodeState::CloseDelimited => {
+                match Pin::new(&mut transport).poll_flush(cx) {
+                    Poll::Ready(Err(err)) => {
+                        *self = BodyEncodeState::Failed;
+                        Poll::Ready(Err(err))
+                    }
+                    p => p,
                 }
-                p => p,
-            },
+            }
             BodyEncodeState::Chunked(chunked) => match chunked.poll_flush(transport, cx) {
                 Poll::Ready(Err(err)) => {
                     *self = BodyEncodeState::Failed;
@@ -146,23 +304,146 @@ impl BodyEncodeState {
             BodyEncodeState::Closed => err_kind(io::ErrorKind::BrokenPipe),
         }
     }
-    fn poll_close<IO: AsyncWrite + Unpin>(
+    fn start_frame(&mut self, data: Bytes) -> io::Result<Frame> {
+        let mut frame = Frame {
+            head: [0u8; 18],
+            head_start: 18,
+            data,
+            tail: b"",
+        };
+        match self {
+            BodyEncodeState::Fixed { remaining, .. } => {
+                match *remaining < frame.data.len() as u64 {
+                    true => return Err(io::ErrorKind::InvalidData.into()),
+                    false => *remaining -= frame.data.len() as u64,
+                }
+            }
+            BodyEncodeState::Chunked(chunked) if chunked.encoder.is_closing() => {
+                return Err(io::ErrorKind::InvalidData.into())
+            }
+            BodyEncodeState::Chunked(_) => {
+                frame.head[16..18].copy_from_slice(b"\r\n");
+                frame.head_start = size_digits(frame.data.len(), &mut frame.head[0..16]);
+                frame.tail = b"\r\n";
+            }
+            BodyEncodeState::CloseDelimited => {}
+            BodyEncodeState::Failed | BodyEncodeState::Closed => {
+                return Err(io::ErrorKind::BrokenPipe.into())
+            }
+        }
+        Ok(frame)
+    }
+    fn poll_frame<IO: AsyncWrite + Unpin>(
         &mut self,
+        frame: &mut Option<Frame>,
         mut transport: IO,
         cx: &mut Context<'_>,
     ) -> Poll<io::Result<()>> {
-        match self {
-            BodyEncodeState::Fixed { .. } => match Pin::new(&mut transport).poll_close(cx) {
-                Poll::Ready(Err(err)) => {
-                    *self = BodyEncodeState::Failed;
-                    Poll::Ready(Err(err))
-                }
+        let p = match (frame.as_mut(), &mut *self) {
+            (Some(frame), _) => frame.poll_write(&mut transport, cx),
+            (None, BodyEncodeState::Chunked(chunked)) => chunked.poll_buffer(&mut transport, cx),
+            (None, _) => return Poll::Ready(Ok(())),
+        };
+        match p {
+            Poll::Ready(Ok(())) if frame.is_some() => {
+                *frame = None;
+                self.poll_frame(frame, transport, cx)
+            }
+            Poll::Ready(Err(err)) => {
+                *frame = None;
+                *self = BodyEncodeState::Failed;
+                Poll::Ready(Err(err))
+            }
+            p => p,
+        }
+    }
+    /// Completes the body without closing the transport, so the connection can carry the next
+    /// message. Close-delimited bodies can only be completed by closing the transport.
+    pub fn poll_finish<IO: AsyncWrite + Unpin>(
+        &mut self,
+        mut transport: IO,
+        cx: &mut Context<'_>,
+    ) -> Poll<io::Result<()>> {
+        let p = match self {
+            BodyEncodeState::Fixed { remaining: 0, .. } => Pin::new(&mut transport).poll_flush(cx),
+            BodyEncodeState::Fixed { .. } => {
+                *self = BodyEncodeState::Failed;
+                return err_kind(io::ErrorKind::InvalidData);
+            }
+            BodyEncodeState::Chunked(chunked) => chunked.poll_finish(transport, cx),
+            BodyEncodeState::CloseDelimited => return self.poll_close(transport, cx),
+            BodyEncodeState::Failed => return err_kind(io::ErrorKind::BrokenPipe),
+            BodyEncodeState::Closed => return Poll::Ready(Ok(())),
+        };
+        match p {
+            Poll::Ready(Err(err)) => {
+                *self = BodyEncodeState::Failed;
+                Poll::Ready(Err(err))
+            }
+            Poll::Ready(Ok(())) => {
+                *self = BodyEncodeState::Closed;
+                Poll::Ready(Ok(()))
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+    fn poll_finish_with_trailers<IO: AsyncWrite + Unpin>(
+        &mut self,
+        mut transport: IO,
+        cx: &mut Context<'_>,
+        trailers: &HeaderMap,
+        last_chunk: &mut Option<BufferWriteState>,
+    ) -> Poll<io::Result<()>> {
+        let BodyEncodeState::Chunked(chunked) = self else {
+            return self.poll_finish(transport, cx);
+        };
+        let p = match last_chunk {
+            None => match chunked.poll_buffer(&mut transport, cx) {
                 Poll::Ready(Ok(())) => {
-                    *self = BodyEncodeState::Closed;
-                    Poll::Ready(Ok(()))
+                    let mut section = Vec::new();
+                    let chunk = header_encode(&mut section, trailers)
+                        .and_then(|()| Ok(chunked.encoder.finish_with_trailers(&section)?));
+                    *last_chunk = Some(BufferWriteState::new(chunk));
+                    return self.poll_finish_with_trailers(transport, cx, trailers, last_chunk);
                 }
-                Poll::Pending => Poll::Pending,
+                p => p,
+            },
+            Some(write) => match write.poll(cx, &mut transport) {
+                Poll::Ready(Ok(())) => Pin::new(&mut transport).poll_flush(cx),
+                p => p,
             },
+        };
+        match p {
+            Poll::Ready(Err(err)) => {
+                *self = BodyEncodeState::Failed;
+                Poll::Ready(Err(err))
+            }
+            Poll::Ready(Ok(())) => {
+                *self = BodyEncodeState::Closed;
+                Poll::Ready(Ok(()))
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+    pub(crate) fn poll_close<IO: AsyncWrite + Unpin>(
+        &mut self,
+        mut transport: IO,
+        cx: &mut Context<'_>,
+    ) -> Poll<io::Result<()>> {
+        match self {
+            BodyEncodeState::Fixed { .. } | BodyEncodeState::CloseDelimited => {
+                match Pin::new(&mut transport).poll_close(cx) {
+                    Poll::Ready(Err(err)) => {
+                        *self = BodyEncodeState::Failed;
+                        Poll::Ready(Err(err))
+                    }
+                    Poll::Ready(Ok(())) => {
+                        *self = BodyEncodeState::Closed;
+                        Poll::Ready(Ok(()))
+                    }
+                    Poll::Pending => Poll::Pending,
+                }
+            }
             BodyEncodeState::Chunked(chunked) => match chunked.poll_close(transport, cx) {
                 Poll::Ready(Err(err)) => {
                     *self = BodyEncodeState::Failed;
@@ -181,15 +462,9 @@ impl BodyEncodeState {
 }
 
 pub struct Chunked {
-    buffer: [u8; 1300],
-    buffered: usize,
-    written: Option<usize>,
-    closing: bool,
+    encoder: ChunkedEncoder,
 }
 
-const BUFFER_HEAD: usize = 5;
-const BUFFER_TAIL: usize = 2;
-
 impl Chunked {
     fn poll_write<IO: AsyncWrite + Unpin>(
         &mut self,
@@ -198,13 +473,7 @@ impl Chunked {
         buf: &[u8],
     ) -> Poll<io::Result<usize>> {
         loop {
-            if self.closing && buf.len() > 0 {
-                return err_kind(io::ErrorKind::InvalidData);
-            }
-            let mut n = 0;
-            if self.written == None {
-                n += self.append(buf);
-            }
+            let n = self.encoder.push(buf)?;
             return match self.poll(&mut transport, cx) {
                 Poll::Pending => match n {
                     0 => Poll::Pending,
@@ -223,85 +492,116 @@ impl Chunked {
         mut transport: IO,
         cx: &mut Context<'_>,
     ) -> Poll<io::Result<()>> {
-        if self.buffered > 0 && self.written == None {
-            self.finalize_chunk();
+        match self.poll_buffer(&mut transport, cx) {
+            Poll::Ready(Ok(())) => Pin::new(&mut transport).poll_flush(cx),
+            p => p,
         }
-        while self.written != None {
+    }
+    fn poll_buffer<IO: AsyncWrite + Unpin>(
+        &mut self,
+        mut transport: IO,
+        cx: &mut Context<'_>,
+    ) -> Poll<io::Result<()>> {
+        self.encoder.flush();
+        while !self.encoder.pending().is_empty() {
             match self.poll(Pin::new(&mut transport), cx) {
                 Poll::Ready(Ok(())) => {}
                 p => return p,
             }
         }
-        Pin::new(&mut transport).poll_flush(cx)
+        Poll::Ready(Ok(()))
+    }
+    fn poll_finish<IO: AsyncWrite + Unpin>(
+        &mut self,
+        mut transport: IO,
+        cx: &mut Context<'_>,
+    ) -> Poll<io::Result<()>> {
+        match self.poll_terminate(&mut transport, cx) {
+            Poll::Ready(Ok(())) => Pin::new(&mut transport).poll_flush(cx),
+            p => p,
+        }
     }
     fn poll_close<IO: AsyncWrite + Unpin>(
         &mut self,
         mut transport: IO,
         cx: &mut Context<'_>,
     ) -> Poll<io::Result<()>> {
-        while self.written != None || !self.closing {
-            if self.written == None {
-                if self.buffered == 0 {
-                    self.closing = true;
-                }
-                self.finalize_chunk();
-            }
+        match self.poll_terminate(&mut transport, cx) {
+            Poll::Ready(Ok(())) => Pin::new(&mut transport).poll_close(cx),
+            p => p,
+        }
+    }
+    fn poll_terminate<IO: AsyncWrite + Unpin>(
+        &mut self,
+        mut transport: IO,
+        cx: &mut Context<'_>,
+    ) -> Poll<io::Result<()>> {
+        while !self.encoder.is_done() {
+            self.encoder.finish();
             match self.poll(Pin::new(&mut transport), cx) {
                 Poll::Ready(Ok(())) => {}
                 p => return p,
             }
         }
-        Pin::new(&mut transport).poll_close(cx)
-    }
-    fn append(&mut self, buf: &[u8]) -> usize {
-        let off = BUFFER_HEAD + self.buffered;
-        let n = min(buf.len(), self.buffer.len() - off - BUFFER_TAIL);
-        self.buffer[off..off + n].copy_from_slice(&buf[0..n]);
-        self.buffered += n;
-        if self.buffered + BUFFER_TAIL + BUFFER_HEAD == self.buffer.len() {
-            self.finalize_chunk();
+        Poll::Ready(Ok(()))
+    }
+    fn poll<IO: AsyncWrite + Unpin>(
+        &mut self,
+        mut transport: IO,
+        cx: &mut Context<'_>,
+    ) -> Poll<io::Result<()>> {
+        let pending = self.encoder.pending();
+        if pending.is_empty() {
+            return Poll::Ready(Ok(()));
         }
-        n
-    }
-    fn finalize_chunk(&mut self) {
-        self.buffer[BUFFER_HEAD - 2..BUFFER_HEAD].copy_from_slice(b"\r\n");
-        let end = BUFFER_HEAD + self.buffered + BUFFER_TAIL;
-        self.buffer[end - 2..end].copy_from_slice(b"\r\n");
-        let mut len = self.buffered;
-        let mut start = BUFFER_HEAD - 2;
-        while len > 0 || start == BUFFER_HEAD - 2 {
-            let digit = len & 15;
-            len /= 16;
-            start -= 1;
-            self.buffer[start] = match digit {
-                0..=9 => b'0' + digit as u8,
-                10..=15 => b'A' - 10 + digit as u8,
-                _ => unreachable!(),
-            };
+        match Pin::new(&mut transport).poll_write(cx, pending) {
+            Poll::Ready(Ok(n)) => {
+                self.encoder.advance(n);
+                Poll::Ready(Ok(()))
+            }
+            Poll::Pending => Poll::Pending,
+            Poll::Ready(Err(err)) => Poll::Ready(Err(err)),
         }
-        self.written = Some(start);
     }
-    fn poll<IO: AsyncWrite + Unpin>(
+}
+
+/// A body part submitted through the [`Sink`] interface, written as-is without buffering.
+struct Frame {
+    head: [u8; 18],
+    head_start: usize,
+    data: Bytes,
+    tail: &'static [u8],
+}
+
+impl Frame {
+    fn poll_write<IO: AsyncWrite + Unpin>(
         &mut self,
         mut transport: IO,
         cx: &mut Context<'_>,
     ) -> Poll<io::Result<()>> {
-        match self.written {
-            None => Poll::Ready(Ok(())),
-            Some(written) => {
-                let end = BUFFER_HEAD + self.buffered + BUFFER_TAIL;
-                match Pin::new(&mut transport).poll_write(cx, &self.buffer[written..end]) {
-                    Poll::Ready(Ok(n)) => {
-                        self.written = Some(written + n);
-                        if self.written == Some(end) {
-                            self.buffered = 0;
-                            self.written = None;
-                        }
-                        Poll::Ready(Ok(()))
+        loop {
+            let remainder = if self.head_start < self.head.len() {
+                &self.head[self.head_start..]
+            } else if !self.data.is_empty() {
+                &self.data[..]
+            } else if !self.tail.is_empty() {
+                self.tail
+            } else {
+                return Poll::Ready(Ok(()));
+            };
+            match Pin::new(&mut transport).poll_write(cx, remainder) {
+                Poll::Ready(Ok(0)) => return err_kind(io::ErrorKind::WriteZero),
+                Poll::Ready(Ok(n)) => {
+                    if self.head_start < self.head.len() {
+                        self.head_start += n;
+                    } else if !self.data.is_empty() {
+                        self.data.advance(n);
+                    } else {
+                        self.tail = &self.tail[n..];
                     }
-                    Poll::Pending => Poll::Pending,
-                    Poll::Ready(Err(err)) => Poll::Ready(Err(err)),
                 }
+                Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+                Poll::Pending => return Poll::Pending,
             }
         }
     }
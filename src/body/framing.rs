@@ -0,0 +1,81 @@
+use crate::body::common::length_from_headers;
+use crate::{RequestHead, ResponseHead};
+use anyhow::bail;
+use http::header::{CONNECTION, CONTENT_LENGTH, TRANSFER_ENCODING};
+use http::{HeaderMap, HeaderValue, Method, StatusCode, Version};
+
+/// How the end of a message body is delimited on the wire.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Framing {
+    /// The message has no body and no framing headers.
+    NoBody,
+    /// `Content-Length` framing.
+    Length(u64),
+    /// `Transfer-Encoding: chunked` framing.
+    Chunked,
+    /// The body ends when the connection is closed (responses only).
+    CloseDelimited,
+}
+
+impl Framing {
+    /// Picks the framing for a request body. Chunked request bodies require an HTTP/1.1 peer.
+    pub fn for_request(
+        head: &RequestHead,
+        length: Option<u64>,
+        peer: Version,
+    ) -> anyhow::Result<Self> {
+        let length = length.or(known_length(head.headers()));
+        Ok(match length {
+            Some(0) if !expects_content(&head.method()) => Framing::NoBody,
+            Some(length) => Framing::Length(length),
+            None if peer >= Version::HTTP_11 => Framing::Chunked,
+            None => bail!("request body of unknown length requires an HTTP/1.1 peer"),
+        })
+    }
+    /// Picks the framing for a response body. Bodies of unknown length are close-delimited for
+    /// peers older than HTTP/1.1.
+    pub fn for_response(head: &ResponseHead, length: Option<u64>, peer: Version) -> Self {
+        if !allows_content(head.status()) {
+            return Framing::NoBody;
+        }
+        match length.or(known_length(head.headers())) {
+            Some(length) => Framing::Length(length),
+            None if peer >= Version::HTTP_11 => Framing::Chunked,
+            None => Framing::CloseDelimited,
+        }
+    }
+    /// Replaces the framing headers with the ones matching this framing.
+    pub fn apply(&self, headers: &mut HeaderMap) {
+        headers.remove(CONTENT_LENGTH);
+        headers.remove(TRANSFER_ENCODING);
+        match self {
+            Framing::NoBody => {}
+            Framing::Length(length) => {
+                headers.insert(CONTENT_LENGTH, HeaderValue::from(*length));
+            }
+            Framing::Chunked => {
+                headers.insert(TRANSFER_ENCODING, HeaderValue::from_static("chunked"));
+            }
+            Framing::CloseDelimited => {
+                headers.insert(CONNECTION, HeaderValue::from_static("close"));
+            }
+        }
+    }
+}
+
+fn known_length(headers: &HeaderMap) -> Option<u64> {
+    match headers.contains_key(CONTENT_LENGTH) {
+        true => length_from_headers(headers).ok().flatten(),
+        false => None,
+    }
+}
+
+fn expects_content(method: &Method) -> bool {
+    matches!(*method, Method::POST | Method::PUT | Method::PATCH)
+}
+
+pub(crate) fn allows_content(status: StatusCode) -> bool {
+    !(status.is_informational()
+        || status == StatusCode::NO_CONTENT
+        || status == StatusCode::NOT_MODIFIED)
+}
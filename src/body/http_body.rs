@@ -0,0 +1,206 @@
+use crate::body::decode::poll_read_bytes;
+use crate::{
+    BodyDecode, BodyDecodeState, BodyDecodeWithContinue, BodyDecodeWithContinueState, BodyEncode,
+};
+use ::http_body::{Body, Frame, SizeHint};
+use bytes::{Buf, Bytes};
+use futures::prelude::*;
+use futures::ready;
+use http::HeaderMap;
+use std::borrow::BorrowMut;
+use std::io;
+use std::mem;
+use std::pin::{pin, Pin};
+use std::task::{Context, Poll};
+
+const FRAME_SIZE: usize = 8192;
+
+/// Turns the outcome of a read into a data frame, or the trailers frame once the body is
+/// complete.
+fn frame(
+    state: &mut BodyDecodeState,
+    read: Poll<io::Result<Bytes>>,
+) -> Poll<Option<io::Result<Frame<Bytes>>>> {
+    match ready!(read)? {
+        data if data.is_empty() => Poll::Ready(
+            state
+                .take_trailers()
+                .map(|trailers| Ok(Frame::trailers(trailers))),
+        ),
+        data => Poll::Ready(Some(Ok(Frame::data(data)))),
+    }
+}
+
+fn is_end_stream(state: &BodyDecodeState) -> bool {
+    state.is_done() && state.trailers().is_none()
+}
+
+fn size_hint(state: &BodyDecodeState) -> SizeHint {
+    match state.remaining() {
+        Some(remaining) => SizeHint::with_exact(remaining),
+        None => SizeHint::default(),
+    }
+}
+
+impl<T: BorrowMut<BodyDecodeState> + Unpin, IO: AsyncRead + Unpin> Body for BodyDecode<T, IO> {
+    type Data = Bytes;
+    type Error = io::Error;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<io::Result<Frame<Bytes>>>> {
+        let this = self.get_mut();
+        let mut buffer = mem::take(this.buffer_mut());
+        let read = poll_read_bytes(this, cx, &mut buffer, FRAME_SIZE);
+        *this.buffer_mut() = buffer;
+        frame(this.state_mut(), read)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        is_end_stream(self.state())
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        size_hint(self.state())
+    }
+}
+
+impl<T: BorrowMut<BodyDecodeWithContinueState> + Unpin, IO: AsyncRead + AsyncWrite + Unpin> Body
+    for BodyDecodeWithContinue<T, IO>
+{
+    type Data = Bytes;
+    type Error = io::Error;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<io::Result<Frame<Bytes>>>> {
+        let this = self.get_mut();
+        let mut buffer = mem::take(this.buffer_mut());
+        let read = poll_read_bytes(this, cx, &mut buffer, FRAME_SIZE);
+        *this.buffer_mut() = buffer;
+        frame(this.state_mut().body_mut(), read)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        is_end_stream(self.state().body())
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        size_hint(self.state().body())
+    }
+}
+
+impl<IO: AsyncWrite + Unpin> BodyEncode<IO> {
+    /// Writes all frames of `body` and completes the encoded body without closing the
+    /// transport. Trailers of `body` end a chunked body and are dropped for other framings.
+    pub async fn write_http_body<B>(&mut self, body: B) -> anyhow::Result<()>
+    where
+        B: Body,
+        B::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+    {
+        let mut body = pin!(body);
+        let mut trailers: Option<HeaderMap> = None;
+        while let Some(frame) = future::poll_fn(|cx| body.as_mut().poll_frame(cx)).await {
+            let frame = frame.map_err(|err| anyhow::anyhow!(err.into()))?;
+            match frame.into_data() {
+                Ok(mut data) => {
+                    while data.has_remaining() {
+                        let chunk = data.chunk();
+                        self.write_all(chunk).await?;
+                        let n = chunk.len();
+                        data.advance(n);
+                    }
+                }
+                Err(frame) => {
+                    if let Ok(frame_trailers) = frame.into_trailers() {
+                        trailers
+                            .get_or_insert_with(HeaderMap::new)
+                            .extend(frame_trailers);
+                    }
+                }
+            }
+        }
+        match trailers {
+            Some(trailers) => self.finish_with_trailers(&trailers).await?,
+            None => self.finish().await?,
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{BodyDecode, BodyEncode};
+    use ::http_body::{Body, Frame};
+    use bytes::Bytes;
+    use futures::executor::block_on;
+    use futures::io::Cursor;
+    use futures::stream;
+    use http::{HeaderMap, HeaderValue};
+    use http_body_util::{BodyExt, StreamBody};
+    use std::io;
+
+    const CHUNKED: &[u8] = b"5\r\nhello\r\n0\r\nexpires: never\r\n\r\n";
+
+    fn body(trailers: bool) -> impl Body<Data = Bytes, Error = io::Error> {
+        let mut map = HeaderMap::new();
+        map.insert("expires", HeaderValue::from_static("never"));
+        let mut frames = vec![Ok(Frame::data(Bytes::from_static(b"hello")))];
+        if trailers {
+            frames.push(Ok(Frame::trailers(map)));
+        }
+        StreamBody::new(stream::iter(frames))
+    }
+
+    #[test]
+    fn decode() {
+        block_on(async {
+            let decode = BodyDecode::new(Cursor::new(CHUNKED), None);
+            assert_eq!(decode.size_hint().exact(), None);
+            let collected = decode.collect().await.unwrap();
+            assert_eq!(collected.trailers().unwrap()["expires"], "never");
+            assert_eq!(collected.to_bytes(), "hello");
+
+            let decode = BodyDecode::new(Cursor::new(b"hello"), Some(5));
+            assert_eq!(decode.size_hint().exact(), Some(5));
+            assert_eq!(decode.collect().await.unwrap().to_bytes(), "hello");
+        })
+    }
+
+    #[test]
+    fn decode_frames() {
+        block_on(async {
+            let input: Vec<u8> = (0..20000u32).map(|n| n as u8).collect();
+            let mut decode = BodyDecode::new(Cursor::new(&input), Some(input.len() as u64));
+            let mut decoded = Vec::new();
+            while let Some(frame) = decode.frame().await {
+                let data = frame.unwrap().into_data().unwrap();
+                assert!(!data.is_empty() && data.len() <= 8192);
+                decoded.extend_from_slice(&data);
+            }
+            assert_eq!(decoded, input);
+        })
+    }
+
+    #[test]
+    fn encode() {
+        block_on(async {
+            let mut transport = Cursor::new(Vec::new());
+            let mut encode = BodyEncode::new(&mut transport, None);
+            encode.write_http_body(body(true)).await.unwrap();
+            assert_eq!(transport.into_inner(), CHUNKED);
+
+            let mut transport = Cursor::new(Vec::new());
+            let mut encode = BodyEncode::new(&mut transport, None);
+            encode.write_http_body(body(false)).await.unwrap();
+            assert_eq!(transport.into_inner(), b"5\r\nhello\r\n0\r\n\r\n");
+
+            let mut transport = Cursor::new(Vec::new());
+            let mut encode = BodyEncode::new(&mut transport, Some(5));
+            encode.write_http_body(body(true)).await.unwrap();
+            assert_eq!(transport.into_inner(), b"hello");
+        })
+    }
+}
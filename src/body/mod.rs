@@ -1,18 +1,27 @@
 pub(crate) mod common;
 mod decode;
 mod encode;
+pub(crate) mod framing;
+#[cfg(feature = "http-body")]
+mod http_body;
 
 pub use decode::*;
 pub use encode::*;
+pub use framing::*;
 
 #[cfg(test)]
 mod tests {
     use crate::BodyDecode;
     use crate::BodyEncode;
+    use crate::{Drained, Framing, RequestHead, ResponseHead};
+    use bytes::Bytes;
     use futures::executor::block_on;
     use futures::io::Cursor;
     use futures::prelude::*;
+    use http::header::{CONNECTION, CONTENT_LENGTH, TRANSFER_ENCODING};
+    use http::{HeaderMap, Method, StatusCode, Uri, Version};
     use rand::{thread_rng, Rng};
+    use std::borrow::Cow;
     use std::io::SeekFrom;
 
     const CHUNKED: &[u8] = b"\
@@ -77,7 +86,7 @@ world! hello world!\r\n\
             let mut transport = Cursor::new(Vec::new());
             let mut encode = BodyEncode::new(&mut transport, None);
             for part in CHUNKED_PARTS {
-                encode.write(part).await.unwrap();
+                encode.write_all(part).await.unwrap();
                 encode.flush().await.unwrap();
             }
             encode.close().await.unwrap();
@@ -108,4 +117,196 @@ world! hello world!\r\n\
             assert_eq!(&input, &output[..]);
         })
     }
+
+    fn response_head(status: StatusCode) -> ResponseHead<'static> {
+        ResponseHead::new(status, Version::HTTP_11, Cow::Owned(HeaderMap::new()))
+    }
+
+    #[test]
+    fn framing_response() {
+        let mut head = response_head(StatusCode::OK);
+        head.headers_mut()
+            .insert(TRANSFER_ENCODING, "chunked".parse().unwrap());
+        let framing = Framing::for_response(&head, Some(5), Version::HTTP_11);
+        assert_eq!(framing, Framing::Length(5));
+        framing.apply(head.headers_mut());
+        assert_eq!(head.headers().get(CONTENT_LENGTH).unwrap(), "5");
+        assert!(head.headers().get(TRANSFER_ENCODING).is_none());
+
+        let head = response_head(StatusCode::OK);
+        assert_eq!(
+            Framing::for_response(&head, None, Version::HTTP_11),
+            Framing::Chunked
+        );
+        assert_eq!(
+            Framing::for_response(&head, None, Version::HTTP_10),
+            Framing::CloseDelimited
+        );
+        let head = response_head(StatusCode::NO_CONTENT);
+        assert_eq!(
+            Framing::for_response(&head, Some(5), Version::HTTP_11),
+            Framing::NoBody
+        );
+    }
+
+    #[test]
+    fn framing_request() {
+        let mut head = RequestHead::new(
+            Method::GET,
+            Cow::Owned(Uri::from_static("/")),
+            Version::HTTP_11,
+            Cow::Owned(HeaderMap::new()),
+        );
+        assert_eq!(
+            Framing::for_request(&head, Some(0), Version::HTTP_11).unwrap(),
+            Framing::NoBody
+        );
+        *head.method_mut() = Method::POST;
+        assert_eq!(
+            Framing::for_request(&head, Some(0), Version::HTTP_11).unwrap(),
+            Framing::Length(0)
+        );
+        assert_eq!(
+            Framing::for_request(&head, None, Version::HTTP_11).unwrap(),
+            Framing::Chunked
+        );
+        assert!(Framing::for_request(&head, None, Version::HTTP_10).is_err());
+    }
+
+    #[test]
+    fn encode_close_delimited() {
+        block_on(async {
+            let mut head = response_head(StatusCode::OK);
+            let mut transport = Cursor::new(Vec::new());
+            let mut encode =
+                BodyEncode::for_response(&mut head, None, Version::HTTP_10, &mut transport);
+            encode.write_all(DECODED).await.unwrap();
+            encode.close().await.unwrap();
+            assert_eq!(head.headers().get(CONNECTION).unwrap(), "close");
+            assert!(head.headers().get(TRANSFER_ENCODING).is_none());
+            assert_eq!(transport.into_inner(), DECODED);
+        })
+    }
+
+    #[test]
+    fn encode_chunked_to_http10_peer() {
+        block_on(async {
+            let mut head = response_head(StatusCode::OK);
+            head.headers_mut()
+                .insert(TRANSFER_ENCODING, "chunked".parse().unwrap());
+            head.headers_mut()
+                .insert(CONNECTION, "keep-alive".parse().unwrap());
+            let mut transport = Cursor::new(Vec::new());
+            let mut encode = BodyEncode::from_response_head(
+                &mut head,
+                &Method::GET,
+                Version::HTTP_10,
+                &mut transport,
+            )
+            .unwrap();
+            encode.write_all(DECODED).await.unwrap();
+            encode.close().await.unwrap();
+            assert_eq!(head.headers().get(CONNECTION).unwrap(), "close");
+            assert!(head.headers().get(TRANSFER_ENCODING).is_none());
+            assert_eq!(transport.into_inner(), DECODED);
+        })
+    }
+
+    #[test]
+    fn encode_response_without_body() {
+        block_on(async {
+            let mut head = response_head(StatusCode::OK);
+            head.headers_mut().insert(CONTENT_LENGTH, 5.into());
+            let mut transport = Cursor::new(Vec::new());
+            let mut encode = BodyEncode::from_response_head(
+                &mut head,
+                &Method::HEAD,
+                Version::HTTP_11,
+                &mut transport,
+            )
+            .unwrap();
+            assert!(encode.write_all(DECODED).await.is_err());
+            assert_eq!(head.headers().get(CONTENT_LENGTH).unwrap(), "5");
+
+            let mut head = response_head(StatusCode::OK);
+            head.headers_mut()
+                .insert(TRANSFER_ENCODING, "chunked".parse().unwrap());
+            let mut encode = BodyEncode::from_response_head(
+                &mut head,
+                &Method::CONNECT,
+                Version::HTTP_11,
+                &mut transport,
+            )
+            .unwrap();
+            encode.close().await.unwrap();
+            assert!(head.headers().get(TRANSFER_ENCODING).is_none());
+            assert!(transport.into_inner().is_empty());
+        })
+    }
+
+    #[test]
+    fn drain() {
+        block_on(async {
+            let mut decode = BodyDecode::new(Cursor::new(DECODED), Some(10));
+            let mut prefix = [0u8; 4];
+            decode.read_exact(&mut prefix).await.unwrap();
+            assert_eq!(decode.drain(5).await.unwrap(), Drained::MustClose);
+            assert_eq!(decode.drain(6).await.unwrap(), Drained::Complete);
+            let (state, transport) = decode.into_inner();
+            assert!(state.is_done());
+            assert_eq!(state.remaining(), Some(0));
+            assert_eq!(transport.position(), 10);
+
+            let mut decode = BodyDecode::new(Cursor::new(CHUNKED), None);
+            assert_eq!(decode.drain(10).await.unwrap(), Drained::MustClose);
+            let mut decode = BodyDecode::new(Cursor::new(CHUNKED), None);
+            assert_eq!(
+                decode.drain(DECODED.len() as u64).await.unwrap(),
+                Drained::Complete
+            );
+            assert_eq!(decode.into_inner().1.position(), CHUNKED.len() as u64);
+            let mut decode = BodyDecode::new(Cursor::new(CHUNKED), None);
+            assert_eq!(decode.drain(u64::MAX).await.unwrap(), Drained::Complete);
+        })
+    }
+
+    #[test]
+    fn decode_stream() {
+        block_on(async {
+            let parts: Vec<_> = BodyDecode::new(Cursor::new(CHUNKED), None)
+                .into_stream(10)
+                .try_collect()
+                .await
+                .unwrap();
+            assert_eq!(parts.concat(), DECODED);
+            assert!(parts.iter().all(|part| part.len() <= 10));
+        })
+    }
+
+    #[test]
+    #[should_panic]
+    fn decode_stream_empty_chunks() {
+        BodyDecode::new(Cursor::new(CHUNKED), None).into_stream(0);
+    }
+
+    #[test]
+    fn encode_sink() {
+        block_on(async {
+            let mut transport = Cursor::new(Vec::new());
+            let mut sink = BodyEncode::new(&mut transport, None).into_sink();
+            for part in CHUNKED_PARTS {
+                sink.feed(Bytes::from_static(part)).await.unwrap();
+            }
+            sink.close().await.unwrap();
+            assert_eq!(transport.into_inner(), CHUNKED);
+
+            let mut transport = Cursor::new(Vec::new());
+            let mut encode = BodyEncode::new(&mut transport, Some(DECODED.len() as u64));
+            encode.write_all(&DECODED[0..6]).await.unwrap();
+            let mut sink = encode.into_sink();
+            sink.send(Bytes::from_static(&DECODED[6..])).await.unwrap();
+            assert!(sink.send(Bytes::from_static(b"!")).await.is_err());
+            assert_eq!(transport.into_inner(), DECODED);
+        })
+    }
 }
@@ -0,0 +1,43 @@
+use core::fmt;
+
+/// Error of the sans-IO codec core. Converts into [`std::io::Error`] with the `std` feature.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum Error {
+    /// The head could not be parsed by `httparse`.
+    Parse(httparse::Error),
+    /// The head is too long or invalid.
+    InvalidHead(&'static str),
+    /// The framing of the body or its trailer section is invalid.
+    InvalidBody(&'static str),
+    /// The input ended before the message was complete.
+    UnexpectedEof,
+    /// The codec failed earlier and can't continue.
+    Failed,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Parse(err) => err.fmt(f),
+            Error::InvalidHead(msg) | Error::InvalidBody(msg) => f.write_str(msg),
+            Error::UnexpectedEof => f.write_str("unexpected end of input"),
+            Error::Failed => f.write_str("codec failed earlier"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {}
+
+#[cfg(feature = "std")]
+impl From<Error> for std::io::Error {
+    fn from(err: Error) -> Self {
+        use std::io::ErrorKind;
+        match err {
+            Error::UnexpectedEof => ErrorKind::UnexpectedEof.into(),
+            Error::Failed => ErrorKind::BrokenPipe.into(),
+            err => std::io::Error::new(ErrorKind::InvalidData, err),
+        }
+    }
+}
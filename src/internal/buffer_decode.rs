@@ -1,69 +1,47 @@
 use crate::internal::io_future::{IoFutureWithOutput, IoFutureWithOutputState};
-use crate::RequestHead;
+use crate::{HeadDecodeFn, HeadDecoder, RequestHead};
 use futures::prelude::*;
 use std::io;
-use std::io::ErrorKind::InvalidData;
-use std::marker::PhantomData;
 use std::pin::Pin;
 use std::task::{Context, Poll};
 
 pub struct BufferDecodeState<O: 'static> {
-    buffer: Vec<u8>,
-    completion: usize,
-    max_headers: usize,
-    decode_func: &'static (dyn Fn(&[u8], usize) -> io::Result<O> + Sync),
-    _phantom: PhantomData<&'static O>,
+    decoder: HeadDecoder<O>,
 }
 
 impl<O> BufferDecodeState<O> {
     pub fn new(
         max_buffer: usize,
         max_headers: usize,
-        decode_func: &'static (dyn Fn(&[u8], usize) -> io::Result<O> + Sync),
+        decode_func: &'static HeadDecodeFn<O>,
     ) -> Self {
-        Self {
-            buffer: Vec::with_capacity(max_buffer),
-            completion: 0,
-            max_headers,
-            decode_func,
-            _phantom: Default::default(),
-        }
+        Self::from_decoder(HeadDecoder::new(max_buffer, max_headers, decode_func))
+    }
+    pub fn from_decoder(decoder: HeadDecoder<O>) -> Self {
+        Self { decoder }
+    }
+    /// Bytes of the head read so far.
+    pub fn buffered(&self) -> &[u8] {
+        self.decoder.buffered()
     }
 }
 
 impl<IO: AsyncRead + Unpin, O> IoFutureWithOutputState<IO, O> for BufferDecodeState<O> {
     fn poll(&mut self, cx: &mut Context<'_>, transport: &mut IO) -> Poll<io::Result<O>> {
-        const END: &[u8; 4] = b"\r\n\r\n";
-        let mut chunk = [0u8; END.len()];
+        let mut chunk = [0u8; 4];
         loop {
-            let chunk = &mut chunk[self.completion..4];
-            if self.buffer.len() + chunk.len() > self.buffer.capacity() {
-                return Poll::Ready(Err(io::Error::new(InvalidData, "head too long")));
-            }
+            let chunk = &mut chunk[..self.decoder.read_hint()];
             match Pin::new(&mut *transport).poll_read(cx, chunk) {
                 Poll::Ready(Ok(0)) => return Poll::Ready(Err(io::ErrorKind::UnexpectedEof.into())),
-                Poll::Ready(Ok(n)) => {
-                    let mut chunk = &chunk[0..n];
-                    self.buffer.extend_from_slice(chunk);
-                    while self.completion == 0 && chunk.len() > 0 {
-                        if chunk[0] == END[0] {
-                            self.completion = 1
-                        }
-                        chunk = &chunk[1..];
-                    }
-                    match chunk == &END[self.completion..self.completion + chunk.len()] {
-                        true => self.completion += chunk.len(),
-                        false => self.completion = 0,
-                    }
-                    if self.completion == END.len() {
-                        break;
-                    }
-                }
+                Poll::Ready(Ok(n)) => match self.decoder.push(&chunk[..n]) {
+                    Ok((_, Some(head))) => return Poll::Ready(Ok(head)),
+                    Ok((_, None)) => {}
+                    Err(err) => return Poll::Ready(Err(err.into())),
+                },
                 Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
                 Poll::Pending => return Poll::Pending,
             }
         }
-        return Poll::Ready((self.decode_func)(&self.buffer, self.max_headers));
     }
 }
 
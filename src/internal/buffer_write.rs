@@ -17,6 +17,10 @@ impl BufferWriteState {
             completion: 0,
         }
     }
+    /// Whether part of the buffer was written already.
+    pub fn is_started(&self) -> bool {
+        self.completion > 0
+    }
 }
 
 impl<IO: AsyncWrite + Unpin> IoFutureState<IO> for BufferWriteState {
@@ -30,7 +34,7 @@ impl<IO: AsyncWrite + Unpin> IoFutureState<IO> for BufferWriteState {
         };
         loop {
             let remainder = &buffer[self.completion..];
-            match Pin::new(&mut *io).poll_write(cx, &remainder) {
+            match Pin::new(&mut *io).poll_write(cx, remainder) {
                 Poll::Ready(Ok(n)) => {
                     if n == remainder.len() {
                         return Poll::Ready(Ok(()));
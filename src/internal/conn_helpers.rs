@@ -0,0 +1,61 @@
+use http::header::{AsHeaderName, CONNECTION, UPGRADE};
+use http::{HeaderMap, HeaderValue, Version};
+
+pub(crate) fn connection_tokens(headers: &HeaderMap) -> impl Iterator<Item = &str> {
+    header_tokens(headers, CONNECTION)
+}
+
+/// Elements of a comma-separated list header, possibly spread over several fields.
+pub(crate) fn header_tokens(
+    headers: &HeaderMap,
+    name: impl AsHeaderName,
+) -> impl Iterator<Item = &str> {
+    headers
+        .get_all(name)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+}
+
+pub(crate) fn has_connection_token(headers: &HeaderMap, token: &str) -> bool {
+    connection_tokens(headers).any(|t| t.eq_ignore_ascii_case(token))
+}
+
+/// Persistence after a message with the given version and headers (RFC 9112, section 9.3).
+pub(crate) fn is_persistent(version: Version, headers: &HeaderMap) -> bool {
+    match has_connection_token(headers, "close") {
+        true => false,
+        false => version >= Version::HTTP_11 || has_connection_token(headers, "keep-alive"),
+    }
+}
+
+/// Rewrites the `close` and `keep-alive` tokens, preserving any other connection options.
+pub(crate) fn set_persistent(headers: &mut HeaderMap, persistent: bool, version: Version) {
+    let mut tokens: Vec<String> = connection_tokens(headers)
+        .filter(|t| !t.eq_ignore_ascii_case("close") && !t.eq_ignore_ascii_case("keep-alive"))
+        .map(str::to_string)
+        .collect();
+    match persistent {
+        true if version < Version::HTTP_11 => tokens.push("keep-alive".to_string()),
+        true => {}
+        false => tokens.push("close".to_string()),
+    }
+    headers.remove(CONNECTION);
+    if let Ok(value) = HeaderValue::from_str(&tokens.join(", ")) {
+        if !tokens.is_empty() {
+            headers.insert(CONNECTION, value);
+        }
+    }
+}
+
+/// Protocols offered in `Upgrade`, ignored unless listed as a connection option and for
+/// HTTP/1.0 (RFC 9110, section 7.8).
+pub(crate) fn upgrade_protocols(
+    version: Version,
+    headers: &HeaderMap,
+) -> impl Iterator<Item = &str> {
+    let offered = version >= Version::HTTP_11 && has_connection_token(headers, "upgrade");
+    header_tokens(headers, UPGRADE).filter(move |_| offered)
+}
@@ -1,73 +1,70 @@
+use crate::Error;
+use crate::{RawHeader, RawRequestHead, RawResponseHead};
 use crate::{RequestHead, ResponseHead};
 use http::header::HeaderName;
 use http::{HeaderMap, HeaderValue, Method, Request, Response, StatusCode, Uri, Version};
 use httparse::Header;
-use std::error::Error;
-use std::io;
-use std::io::ErrorKind::InvalidData;
 
-#[allow(deprecated)]
-pub fn request_head_parse(buffer: &[u8], max_headers: usize) -> io::Result<RequestHead<'static>> {
-    let mut headers = vec![httparse::EMPTY_HEADER; max_headers];
-    let mut parsed_request = httparse::Request::new(&mut headers);
-    if parsed_request
-        .parse(buffer)
-        .map_err(|err| io::Error::new(InvalidData, err.description()))?
-        .is_partial()
-    {
-        return Err(io::Error::new(InvalidData, "malformed HTTP head"));
-    }
-    if parsed_request.version != Some(1) {
-        return Err(io::Error::new(InvalidData, "unsupported HTTP version"));
-    }
-    let method = Method::from_bytes(parsed_request.method.unwrap_or("").as_bytes())
-        .map_err(|err| io::Error::new(InvalidData, err.description()))?;
-    let uri = parsed_request
-        .path
-        .unwrap_or("")
+pub fn request_head_parse(
+    buffer: &[u8],
+    max_headers: usize,
+) -> Result<RequestHead<'static>, Error> {
+    let raw = RawRequestHead::parse(buffer, max_headers)?;
+    let method = Method::from_bytes(raw.method.as_bytes())
+        .map_err(|_| Error::InvalidHead("invalid method"))?;
+    let uri = raw
+        .target
         .parse::<Uri>()
-        .map_err(|_| io::Error::new(InvalidData, "invalid uri"))?;
+        .map_err(|_| Error::InvalidHead("invalid uri"))?;
     let mut request = Request::new(());
     *request.method_mut() = method;
     *request.uri_mut() = uri;
-    *request.version_mut() = Version::HTTP_11;
-    let headers = request.headers_mut();
-    copy_parsed_headers(headers, parsed_request.headers)?;
+    *request.version_mut() = parsed_version(raw.version)?;
+    copy_raw_headers(request.headers_mut(), &raw.headers)?;
     Ok(RequestHead::from(request))
 }
 
-#[allow(deprecated)]
-pub fn response_head_parse(buffer: &[u8], max_headers: usize) -> io::Result<ResponseHead<'static>> {
-    let mut headers = vec![httparse::EMPTY_HEADER; max_headers];
-    let mut parsed_response = httparse::Response::new(&mut headers);
-    if parsed_response
-        .parse(buffer)
-        .map_err(|err| io::Error::new(InvalidData, err.description()))?
-        .is_partial()
-    {
-        return Err(io::Error::new(InvalidData, "malformed HTTP head"));
-    }
-    if parsed_response.version != Some(1) {
-        return Err(io::Error::new(InvalidData, "unsupported HTTP version"));
-    }
+pub fn response_head_parse(
+    buffer: &[u8],
+    max_headers: usize,
+) -> Result<ResponseHead<'static>, Error> {
+    let raw = RawResponseHead::parse(buffer, max_headers)?;
     let mut response = Response::new(());
-    *response.version_mut() = Version::HTTP_11;
-    *response.status_mut() = StatusCode::from_u16(parsed_response.code.unwrap())
-        .map_err(|_| io::Error::new(InvalidData, "invalid status code"))?;
-    let headers = response.headers_mut();
-    copy_parsed_headers(headers, parsed_response.headers)?;
+    *response.version_mut() = parsed_version(raw.version)?;
+    *response.status_mut() =
+        StatusCode::from_u16(raw.status).map_err(|_| Error::InvalidHead("invalid status code"))?;
+    copy_raw_headers(response.headers_mut(), &raw.headers)?;
     Ok(ResponseHead::from(response))
 }
 
-pub(crate) fn copy_parsed_headers(trg: &mut HeaderMap, parsed: &[Header]) -> io::Result<()> {
+fn parsed_version(version: u8) -> Result<Version, Error> {
+    match version {
+        0 => Ok(Version::HTTP_10),
+        1 => Ok(Version::HTTP_11),
+        _ => Err(Error::InvalidHead("unsupported HTTP version")),
+    }
+}
+
+fn copy_raw_headers(trg: &mut HeaderMap, raw: &[RawHeader]) -> Result<(), Error> {
+    trg.reserve(raw.len());
+    for header in raw {
+        append_header(trg, header.name.as_bytes(), &header.value)?;
+    }
+    Ok(())
+}
+
+pub(crate) fn copy_parsed_headers(trg: &mut HeaderMap, parsed: &[Header]) -> Result<(), Error> {
     trg.reserve(parsed.len());
     for header in parsed {
-        trg.append(
-            HeaderName::from_bytes(header.name.as_bytes())
-                .map_err(|_| io::Error::new(InvalidData, "invalid header name"))?,
-            HeaderValue::from_bytes(header.value)
-                .map_err(|_| io::Error::new(InvalidData, "invalid header value"))?,
-        );
+        append_header(trg, header.name.as_bytes(), header.value)?;
     }
     Ok(())
 }
+
+fn append_header(trg: &mut HeaderMap, name: &[u8], value: &[u8]) -> Result<(), Error> {
+    trg.append(
+        HeaderName::from_bytes(name).map_err(|_| Error::InvalidHead("invalid header name"))?,
+        HeaderValue::from_bytes(value).map_err(|_| Error::InvalidHead("invalid header value"))?,
+    );
+    Ok(())
+}
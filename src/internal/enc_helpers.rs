@@ -1,24 +1,28 @@
-use http::{HeaderMap, StatusCode, Version};
+use crate::{encode_headers, RawHeader};
+use http::{HeaderMap, Version};
+use std::borrow::Cow;
 use std::io;
-use std::io::ErrorKind::InvalidData;
-use std::io::Write;
 
 pub(crate) fn header_encode(buffer: &mut Vec<u8>, headers: &HeaderMap) -> io::Result<()> {
-    for (k, v) in headers {
-        let v = v
-            .to_str()
-            .map_err(|_| io::Error::new(InvalidData, "invalid character in header value"))?;
-        writeln!(buffer, "{}: {}\r", k, v)?;
-    }
-    writeln!(buffer, "\r")?;
-    Ok(())
+    Ok(encode_headers(buffer, &raw_headers(headers))?)
+}
+
+/// Borrows the header fields of a map for the raw head encoders.
+pub(crate) fn raw_headers(headers: &HeaderMap) -> Vec<RawHeader<'_>> {
+    headers
+        .iter()
+        .map(|(name, value)| RawHeader {
+            name: Cow::Borrowed(name.as_str()),
+            value: Cow::Borrowed(value.as_bytes()),
+        })
+        .collect()
 }
 
-pub(crate) fn status_line_encode(
-    buffer: &mut Vec<u8>,
-    version: &Version,
-    status: &StatusCode,
-) -> io::Result<()> {
-    writeln!(buffer, "{:?} {}\r", version, status)?;
-    Ok(())
+/// Minor version of an HTTP/1 head, other versions can't be encoded as one.
+pub(crate) fn raw_version(version: Version) -> io::Result<u8> {
+    match version {
+        Version::HTTP_10 => Ok(0),
+        Version::HTTP_11 => Ok(1),
+        _ => Err(crate::Error::InvalidHead("unsupported HTTP version").into()),
+    }
 }
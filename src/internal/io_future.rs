@@ -46,7 +46,7 @@ pub struct IoFutureWithOutput<S: IoFutureWithOutputState<IO, O>, IO: Sized + Unp
 
 impl<S: IoFutureWithOutputState<IO, O>, IO: Unpin, O> IoFutureWithOutput<S, IO, O> {
     pub fn new(state: S, io: IO) -> Self {
-        IoFutureWithOutput(Some((state, io, PhantomData::default())))
+        IoFutureWithOutput(Some((state, io, PhantomData)))
     }
     pub fn checkpoint(self) -> (S, IO) {
         let (state, io, _) = self.0.unwrap();
@@ -60,7 +60,7 @@ impl<S: IoFutureWithOutputState<IO, O>, IO: Unpin, O> Future for IoFutureWithOut
     fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         let (mut state, mut io, _) = self.0.take().unwrap();
         let p = state.poll(cx, &mut io);
-        self.0 = Some((state, io, PhantomData::default()));
+        self.0 = Some((state, io, PhantomData));
         p.map(|r| r.map(|o| (self.0.take().unwrap().1, o)))
     }
 }
@@ -0,0 +1,111 @@
+use futures::io::Cursor;
+use futures::{AsyncRead, AsyncWrite};
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// transport reading from a fixed input and recording everything written to it
+pub(crate) struct MockIo {
+    pub input: Cursor<Vec<u8>>,
+    pub output: Vec<u8>,
+    pub closed: bool,
+    /// reads are pending until at least this many bytes were written
+    pub hold_input: usize,
+    /// writes are pending once this many bytes were written
+    pub hold_output: Option<usize>,
+    /// reads are pending instead of ending once the input is consumed
+    pub hold_eof: bool,
+}
+
+impl MockIo {
+    pub fn new(input: &[u8]) -> Self {
+        Self {
+            input: Cursor::new(input.to_vec()),
+            output: Vec::new(),
+            closed: false,
+            hold_input: 0,
+            hold_output: None,
+            hold_eof: false,
+        }
+    }
+    pub fn remaining_input(&self) -> &[u8] {
+        &self.input.get_ref()[self.input.position() as usize..]
+    }
+}
+
+impl AsyncRead for MockIo {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        if self.output.len() < self.hold_input
+            || (self.hold_eof && self.remaining_input().is_empty())
+        {
+            cx.waker().wake_by_ref();
+            return Poll::Pending;
+        }
+        Pin::new(&mut self.input).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for MockIo {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        if self.closed {
+            return Poll::Ready(Err(io::ErrorKind::BrokenPipe.into()));
+        }
+        let n = match self.hold_output {
+            Some(limit) if self.output.len() >= limit => {
+                cx.waker().wake_by_ref();
+                return Poll::Pending;
+            }
+            Some(limit) => buf.len().min(limit - self.output.len()),
+            None => buf.len(),
+        };
+        self.output.extend_from_slice(&buf[..n]);
+        Poll::Ready(Ok(n))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.closed = true;
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl tokio::io::AsyncRead for MockIo {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut tokio::io::ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        crate::tokio::poll_read_buf(buf, |buf| AsyncRead::poll_read(self, cx, buf))
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl tokio::io::AsyncWrite for MockIo {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        AsyncWrite::poll_write(self, cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        AsyncWrite::poll_flush(self, cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        AsyncWrite::poll_close(self, cx)
+    }
+}
@@ -1,7 +1,9 @@
 pub mod buffer_decode;
 pub mod buffer_write;
+pub(crate) mod conn_helpers;
 pub(crate) mod dec_helpers;
 pub(crate) mod enc_helpers;
 pub mod io_future;
-pub mod parse_helper;
+#[cfg(test)]
+pub(crate) mod mock_io;
 pub mod terminator;
@@ -4,7 +4,7 @@ pub struct TerminatorOverlap<'a> {
 }
 
 impl<'a> TerminatorOverlap<'a> {
-    pub fn new(terminator: &'a [u8]) -> TerminatorOverlap {
+    pub fn new(terminator: &'a [u8]) -> TerminatorOverlap<'a> {
         TerminatorOverlap {
             terminator,
             overlap: 0,
@@ -1,12 +1,40 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
 extern crate core;
 
+#[cfg(feature = "std")]
+pub mod blocking;
+#[cfg(feature = "std")]
 mod body;
+mod error;
+#[cfg(feature = "std")]
 pub mod internal;
+#[cfg(feature = "std")]
+mod message;
+#[cfg(feature = "std")]
+pub mod proxy_protocol;
+#[cfg(feature = "std")]
 mod request;
+#[cfg(feature = "std")]
 mod response;
+mod sans_io;
+#[cfg(feature = "tokio")]
+pub mod tokio;
+#[cfg(feature = "std")]
 mod transaction;
+#[cfg(feature = "websocket")]
+pub mod websocket;
 
+#[cfg(feature = "std")]
 pub use body::*;
+pub use error::*;
+#[cfg(feature = "std")]
+pub use message::*;
+#[cfg(feature = "std")]
 pub use request::*;
+#[cfg(feature = "std")]
 pub use response::*;
+pub use sans_io::*;
+#[cfg(feature = "std")]
 pub use transaction::*;
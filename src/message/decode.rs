@@ -0,0 +1,56 @@
+use crate::internal::io_future::IoFutureWithOutputState;
+use crate::{
+    BodyDecodeState, BodyDecodeWithContinueState, FinalResponseDecodeState, Limits, RequestHead,
+};
+use anyhow::bail;
+use futures::prelude::*;
+use http::{Method, Request, Response};
+
+/// Decodes a request head and reads the body into memory. `100 Continue` is sent if the
+/// client expects it and the declared body length is within the limits.
+pub async fn decode_request<IO: AsyncRead + AsyncWrite + Unpin>(
+    mut io: IO,
+    limits: &Limits,
+) -> anyhow::Result<Request<Vec<u8>>> {
+    let (_, head) = RequestHead::decode_state_with_limits(limits.max_head, limits.max_headers)
+        .into_future(&mut io)
+        .await?;
+    let state = BodyDecodeWithContinueState::from_head(&head)?;
+    check_length(state.body(), limits)?;
+    let body = read_body(state.into_async_read(&mut io), limits).await?;
+    Ok(Request::from_parts(head.into(), body))
+}
+
+/// Decodes the final response to a `request_method` request and reads the body into memory.
+/// Interim (1xx) heads are skipped.
+pub async fn decode_response<IO: AsyncRead + Unpin>(
+    mut io: IO,
+    request_method: &Method,
+    limits: &Limits,
+) -> anyhow::Result<Response<Vec<u8>>> {
+    let (_, head) = FinalResponseDecodeState::new(limits.max_head, limits.max_headers, |_| {})
+        .into_future(&mut io)
+        .await?;
+    let state = BodyDecodeState::from_response_head(&head, request_method)?;
+    check_length(&state, limits)?;
+    let body = read_body(state.into_async_read(&mut io), limits).await?;
+    Ok(Response::from_parts(head.into(), body))
+}
+
+fn check_length(state: &BodyDecodeState, limits: &Limits) -> anyhow::Result<()> {
+    if matches!(state.remaining(), Some(remaining) if remaining > limits.max_body) {
+        bail!("body exceeds limit of {} bytes", limits.max_body)
+    }
+    Ok(())
+}
+
+async fn read_body(body: impl AsyncRead + Unpin, limits: &Limits) -> anyhow::Result<Vec<u8>> {
+    let mut buffer = Vec::new();
+    body.take(limits.max_body.saturating_add(1))
+        .read_to_end(&mut buffer)
+        .await?;
+    if buffer.len() as u64 > limits.max_body {
+        bail!("body exceeds limit of {} bytes", limits.max_body)
+    }
+    Ok(buffer)
+}
@@ -0,0 +1,64 @@
+use crate::{BodyEncodeState, RequestHead, ResponseHead};
+use futures::prelude::*;
+use http::header::{CONTENT_LENGTH, TRANSFER_ENCODING};
+use http::{HeaderValue, Method, Request, Response, Version};
+
+/// Encodes a request with a complete body for a server speaking `peer`. The framing headers are
+/// replaced by a matching `Content-Length`.
+pub async fn encode_request<IO: AsyncWrite + Unpin, B: AsRef<[u8]>>(
+    mut io: IO,
+    request: &Request<B>,
+    peer: Version,
+) -> anyhow::Result<()> {
+    let body = request.body().as_ref();
+    let mut head = RequestHead::ref_request(request);
+    let state = BodyEncodeState::for_request(&mut head, Some(body.len() as u64), peer)?;
+    head.encode(&mut io).await?;
+    let mut encode = state.into_async_write(&mut io);
+    encode.write_all(body).await?;
+    encode.finish().await?;
+    Ok(())
+}
+
+/// Encodes the response to a `request_method` request with a complete body for a client speaking
+/// `peer`, usually the version of its request. The framing headers are replaced by a matching
+/// `Content-Length`. The body is left out of responses that can't have one, see
+/// [`BodyEncodeState::from_response_head`].
+pub async fn encode_response<IO: AsyncWrite + Unpin, B: AsRef<[u8]>>(
+    io: IO,
+    response: &Response<B>,
+    request_method: &Method,
+    peer: Version,
+) -> anyhow::Result<()> {
+    let mut head = ResponseHead::ref_response(response);
+    write_complete_response(
+        io,
+        &mut head,
+        response.body().as_ref(),
+        request_method,
+        peer,
+    )
+    .await
+}
+
+/// Encodes `head` followed by `body`, with the framing selected for the length of `body`.
+pub(crate) async fn write_complete_response<IO: AsyncWrite + Unpin>(
+    mut io: IO,
+    head: &mut ResponseHead<'_>,
+    body: &[u8],
+    request_method: &Method,
+    peer: Version,
+) -> anyhow::Result<()> {
+    let headers = head.headers_mut();
+    headers.remove(TRANSFER_ENCODING);
+    headers.insert(CONTENT_LENGTH, HeaderValue::from(body.len()));
+    let state = BodyEncodeState::from_response_head(head, request_method, peer)?;
+    let has_body = !matches!(state, BodyEncodeState::Fixed { remaining: 0, .. });
+    head.encode(&mut io).await?;
+    let mut encode = state.into_async_write(&mut io);
+    if has_body {
+        encode.write_all(body).await?;
+    }
+    encode.finish().await?;
+    Ok(())
+}
@@ -0,0 +1,183 @@
+mod decode;
+mod encode;
+
+pub use decode::*;
+pub use encode::*;
+
+/// Size limits applied when decoding complete messages.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Limits {
+    pub max_head: usize,
+    pub max_headers: usize,
+    pub max_body: u64,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_head: 8192,
+            max_headers: 128,
+            max_body: 1 << 20,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{decode_request, decode_response, encode_request, encode_response, Limits};
+    use futures::executor::block_on;
+    use futures::io::Cursor;
+    use http::header::{CONTENT_LENGTH, TRANSFER_ENCODING};
+    use http::{Method, Request, Response, StatusCode, Version};
+
+    const CHUNKED_REQUEST: &[u8] = b"\
+POST /echo HTTP/1.1\r\n\
+transfer-encoding: chunked\r\n\
+\r\n\
+6\r\n\
+hello \r\n\
+6\r\n\
+world!\r\n\
+0\r\n\
+\r\n";
+
+    #[test]
+    fn request_roundtrip() {
+        block_on(async {
+            let request = decode_request(Cursor::new(CHUNKED_REQUEST.to_vec()), &Limits::default())
+                .await
+                .unwrap();
+            assert_eq!(request.method(), Method::POST);
+            assert_eq!(request.body(), b"hello world!");
+
+            let mut transport = Cursor::new(Vec::new());
+            encode_request(&mut transport, &request, Version::HTTP_11)
+                .await
+                .unwrap();
+            transport.set_position(0);
+            let decoded = decode_request(&mut transport, &Limits::default())
+                .await
+                .unwrap();
+            assert_eq!(decoded.headers().get(CONTENT_LENGTH).unwrap(), "12");
+            assert!(decoded.headers().get(TRANSFER_ENCODING).is_none());
+            assert_eq!(decoded.body(), b"hello world!");
+        })
+    }
+
+    #[test]
+    fn request_body_limit() {
+        block_on(async {
+            let limits = Limits {
+                max_body: 11,
+                ..Limits::default()
+            };
+            assert!(
+                decode_request(Cursor::new(CHUNKED_REQUEST.to_vec()), &limits)
+                    .await
+                    .is_err()
+            );
+            let request = Request::post("/").body("hello world!").unwrap();
+            let mut transport = Cursor::new(Vec::new());
+            encode_request(&mut transport, &request, Version::HTTP_11)
+                .await
+                .unwrap();
+            transport.set_position(0);
+            assert!(decode_request(&mut transport, &limits).await.is_err());
+        })
+    }
+
+    #[test]
+    fn response_roundtrip() {
+        block_on(async {
+            let response = Response::builder()
+                .status(StatusCode::CREATED)
+                .body(b"hello world!".to_vec())
+                .unwrap();
+            let mut transport = Cursor::new(Vec::new());
+            encode_response(&mut transport, &response, &Method::GET, Version::HTTP_11)
+                .await
+                .unwrap();
+            transport.set_position(0);
+            let decoded = decode_response(&mut transport, &Method::GET, &Limits::default())
+                .await
+                .unwrap();
+            assert_eq!(decoded.status(), StatusCode::CREATED);
+            assert_eq!(decoded.body(), b"hello world!");
+        })
+    }
+
+    #[test]
+    fn response_close_delimited() {
+        block_on(async {
+            const INPUT: &[u8] = b"HTTP/1.0 200 OK\r\n\r\nhello world!";
+            let response = decode_response(Cursor::new(INPUT), &Method::GET, &Limits::default())
+                .await
+                .unwrap();
+            assert_eq!(response.body(), b"hello world!");
+        })
+    }
+
+    #[test]
+    fn response_to_head() {
+        block_on(async {
+            const INPUT: &[u8] = b"HTTP/1.1 200 OK\r\ncontent-length: 12\r\n\r\nnext";
+            let mut transport = Cursor::new(INPUT);
+            let response = decode_response(&mut transport, &Method::HEAD, &Limits::default())
+                .await
+                .unwrap();
+            assert_eq!(response.headers().get(CONTENT_LENGTH).unwrap(), "12");
+            assert!(response.body().is_empty());
+            assert_eq!(transport.position(), INPUT.len() as u64 - 4);
+        })
+    }
+
+    async fn encoded_response(
+        status: StatusCode,
+        request_method: &Method,
+        body: &'static str,
+    ) -> String {
+        let mut response = Response::new(body);
+        *response.status_mut() = status;
+        let mut transport = Cursor::new(Vec::new());
+        encode_response(&mut transport, &response, request_method, Version::HTTP_11)
+            .await
+            .unwrap();
+        String::from_utf8(transport.into_inner()).unwrap()
+    }
+
+    #[test]
+    fn response_without_body() {
+        block_on(async {
+            assert_eq!(
+                encoded_response(StatusCode::OK, &Method::HEAD, "hello").await,
+                "HTTP/1.1 200 OK\r\ncontent-length: 5\r\n\r\n"
+            );
+            assert_eq!(
+                encoded_response(StatusCode::OK, &Method::CONNECT, "hello").await,
+                "HTTP/1.1 200 OK\r\n\r\n"
+            );
+            assert_eq!(
+                encoded_response(StatusCode::NO_CONTENT, &Method::GET, "hello").await,
+                "HTTP/1.1 204 No Content\r\n\r\n"
+            );
+            assert_eq!(
+                encoded_response(StatusCode::FORBIDDEN, &Method::CONNECT, "no").await,
+                "HTTP/1.1 403 Forbidden\r\ncontent-length: 2\r\n\r\nno"
+            );
+        })
+    }
+
+    #[test]
+    fn unlimited_body() {
+        block_on(async {
+            let limits = Limits {
+                max_body: u64::MAX,
+                ..Limits::default()
+            };
+            let request = decode_request(Cursor::new(CHUNKED_REQUEST.to_vec()), &limits)
+                .await
+                .unwrap();
+            assert_eq!(request.body(), b"hello world!");
+        })
+    }
+}
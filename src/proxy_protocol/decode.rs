@@ -0,0 +1,251 @@
+use crate::internal::io_future::{IoFutureWithOutput, IoFutureWithOutputState};
+use crate::proxy_protocol::{ProxyCommand, ProxyHeader, ProxyTlv, ProxyVersion, V2_SIGNATURE};
+use futures::AsyncRead;
+use std::io;
+use std::io::ErrorKind::InvalidData;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+const V1_MAX_LEN: usize = 107;
+const V2_HEAD_LEN: usize = 16;
+
+/// Reads exactly the bytes of a PROXY header. Both versions are recognized from the first
+/// 12 bytes, which is shorter than any valid header.
+pub struct ProxyHeaderDecodeState {
+    buffer: Vec<u8>,
+    completion: usize,
+}
+
+impl ProxyHeaderDecodeState {
+    pub fn new() -> Self {
+        Self {
+            buffer: vec![0u8; V2_SIGNATURE.len()],
+            completion: 0,
+        }
+    }
+    /// Grows the buffer to the bytes known to follow, or parses the complete header.
+    fn next(&mut self) -> io::Result<Option<ProxyHeader>> {
+        let buffer = &self.buffer;
+        if buffer.starts_with(V2_SIGNATURE) {
+            if buffer.len() == V2_SIGNATURE.len() {
+                self.buffer.resize(V2_HEAD_LEN, 0);
+                return Ok(None);
+            }
+            let len = u16::from_be_bytes([buffer[14], buffer[15]]) as usize;
+            if buffer.len() < V2_HEAD_LEN + len {
+                self.buffer.resize(V2_HEAD_LEN + len, 0);
+                return Ok(None);
+            }
+            return parse_v2(buffer).map(Some);
+        }
+        if buffer.starts_with(b"PROXY ") {
+            if buffer.ends_with(b"\r\n") {
+                return parse_v1(buffer).map(Some);
+            }
+            if buffer.len() == V1_MAX_LEN {
+                return Err(io::Error::new(InvalidData, "PROXY header too long"));
+            }
+            self.buffer.push(0);
+            return Ok(None);
+        }
+        Err(io::Error::new(InvalidData, "missing PROXY header"))
+    }
+}
+
+impl Default for ProxyHeaderDecodeState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<IO: AsyncRead + Unpin> IoFutureWithOutputState<IO, ProxyHeader> for ProxyHeaderDecodeState {
+    fn poll(&mut self, cx: &mut Context<'_>, io: &mut IO) -> Poll<io::Result<ProxyHeader>> {
+        loop {
+            if self.completion == self.buffer.len() {
+                match self.next() {
+                    Ok(Some(header)) => return Poll::Ready(Ok(header)),
+                    Ok(None) => continue,
+                    Err(err) => return Poll::Ready(Err(err)),
+                }
+            }
+            match Pin::new(&mut *io).poll_read(cx, &mut self.buffer[self.completion..]) {
+                Poll::Ready(Ok(0)) => return Poll::Ready(Err(io::ErrorKind::UnexpectedEof.into())),
+                Poll::Ready(Ok(n)) => self.completion += n,
+                Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+pub type ProxyHeaderDecode<IO> = IoFutureWithOutput<ProxyHeaderDecodeState, IO, ProxyHeader>;
+
+fn invalid() -> io::Error {
+    io::Error::new(InvalidData, "invalid PROXY header")
+}
+
+fn parse_v1(buffer: &[u8]) -> io::Result<ProxyHeader> {
+    let line = std::str::from_utf8(&buffer[..buffer.len() - 2]).map_err(|_| invalid())?;
+    let fields: Vec<&str> = line.split(' ').collect();
+    let addresses = match fields.get(1).copied() {
+        Some("UNKNOWN") => return Ok(ProxyHeader::local(ProxyVersion::V1)),
+        Some("TCP4") | Some("TCP6") if fields.len() == 6 => {
+            let ip = |s: &str| s.parse::<IpAddr>().map_err(|_| invalid());
+            let port = |s: &str| s.parse::<u16>().map_err(|_| invalid());
+            let source = SocketAddr::new(ip(fields[2])?, port(fields[4])?);
+            let destination = SocketAddr::new(ip(fields[3])?, port(fields[5])?);
+            if source.is_ipv4() != (fields[1] == "TCP4")
+                || destination.is_ipv4() != source.is_ipv4()
+            {
+                return Err(invalid());
+            }
+            (source, destination)
+        }
+        _ => return Err(invalid()),
+    };
+    Ok(ProxyHeader::new(ProxyVersion::V1, addresses.0, addresses.1))
+}
+
+fn parse_v2(buffer: &[u8]) -> io::Result<ProxyHeader> {
+    let command = match buffer[12] {
+        0x20 => ProxyCommand::Local,
+        0x21 => ProxyCommand::Proxy,
+        _ => return Err(invalid()),
+    };
+    // Only stream transports (low nibble 1) can carry HTTP. LOCAL connections ignore the byte.
+    if command == ProxyCommand::Proxy && !matches!(buffer[13], 0x00 | 0x11 | 0x21 | 0x31) {
+        return Err(invalid());
+    }
+    let payload = &buffer[V2_HEAD_LEN..];
+    let (addresses, address_len) = match buffer[13] >> 4 {
+        0x0 => (None, 0),
+        0x1 if payload.len() >= 12 => {
+            let ip = |b: &[u8]| IpAddr::V4(Ipv4Addr::new(b[0], b[1], b[2], b[3]));
+            let source = SocketAddr::new(ip(&payload[0..4]), port(&payload[8..10]));
+            let destination = SocketAddr::new(ip(&payload[4..8]), port(&payload[10..12]));
+            (Some((source, destination)), 12)
+        }
+        0x2 if payload.len() >= 36 => {
+            let ip = |b: &[u8]| IpAddr::V6(Ipv6Addr::from(<[u8; 16]>::try_from(b).unwrap()));
+            let source = SocketAddr::new(ip(&payload[0..16]), port(&payload[32..34]));
+            let destination = SocketAddr::new(ip(&payload[16..32]), port(&payload[34..36]));
+            (Some((source, destination)), 36)
+        }
+        // unix socket addresses have no socket address representation
+        0x3 if payload.len() >= 216 => (None, 216),
+        _ => return Err(invalid()),
+    };
+    let mut tlvs = Vec::new();
+    let mut rest = &payload[address_len..];
+    while !rest.is_empty() {
+        if rest.len() < 3 {
+            return Err(invalid());
+        }
+        let len = u16::from_be_bytes([rest[1], rest[2]]) as usize;
+        let value = rest.get(3..3 + len).ok_or_else(invalid)?;
+        tlvs.push(ProxyTlv {
+            kind: rest[0],
+            value: value.to_vec(),
+        });
+        rest = &rest[3 + len..];
+    }
+    Ok(ProxyHeader {
+        version: ProxyVersion::V2,
+        addresses: addresses.filter(|_| command == ProxyCommand::Proxy),
+        command,
+        tlvs,
+    })
+}
+
+fn port(bytes: &[u8]) -> u16 {
+    u16::from_be_bytes([bytes[0], bytes[1]])
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::proxy_protocol::{ProxyHeader, ProxyTlv, ProxyVersion};
+    use crate::RequestHead;
+    use futures::executor::block_on;
+    use futures::io::Cursor;
+
+    const REQUEST: &[u8] = b"GET / HTTP/1.1\r\nhost: example.com\r\n\r\n";
+
+    #[test]
+    fn v1() {
+        block_on(async {
+            let input = [b"PROXY TCP4 192.0.2.1 198.51.100.2 56324 443\r\n", REQUEST].concat();
+            let (io, header) = ProxyHeader::decode(Cursor::new(input)).await.unwrap();
+            assert_eq!(header.source(), Some("192.0.2.1:56324".parse().unwrap()));
+            assert_eq!(
+                header.destination(),
+                Some("198.51.100.2:443".parse().unwrap())
+            );
+            let (_, head) = RequestHead::decode(io).await.unwrap();
+            assert_eq!(head.uri(), "/");
+
+            let input = b"PROXY UNKNOWN ffff::1 ffff::2 1 2\r\n";
+            let (_, header) = ProxyHeader::decode(Cursor::new(input)).await.unwrap();
+            assert_eq!(header, ProxyHeader::local(ProxyVersion::V1));
+            assert!(ProxyHeader::decode(Cursor::new(REQUEST)).await.is_err());
+        })
+    }
+
+    #[test]
+    fn round_trip() {
+        block_on(async {
+            let mut headers = vec![
+                ProxyHeader::new(
+                    ProxyVersion::V1,
+                    "[2001:db8::1]:1234".parse().unwrap(),
+                    "[2001:db8::2]:80".parse().unwrap(),
+                ),
+                ProxyHeader::new(
+                    ProxyVersion::V2,
+                    "192.0.2.1:56324".parse().unwrap(),
+                    "198.51.100.2:443".parse().unwrap(),
+                ),
+                ProxyHeader::new(
+                    ProxyVersion::V2,
+                    "[2001:db8::1]:1234".parse().unwrap(),
+                    "[2001:db8::2]:80".parse().unwrap(),
+                ),
+                ProxyHeader::local(ProxyVersion::V2),
+            ];
+            headers[1].tlvs.push(ProxyTlv {
+                kind: 0x02,
+                value: b"example.com".to_vec(),
+            });
+            for header in headers {
+                let mut io = Cursor::new(Vec::new());
+                header.encode(&mut io).await.unwrap();
+                io.get_mut().extend_from_slice(REQUEST);
+                io.set_position(0);
+                let (io, decoded) = ProxyHeader::decode(io).await.unwrap();
+                assert_eq!(decoded, header);
+                let (_, head) = RequestHead::decode(io).await.unwrap();
+                assert_eq!(head.uri(), "/");
+            }
+        })
+    }
+
+    #[test]
+    fn v2_transport() {
+        block_on(async {
+            let header = ProxyHeader::new(
+                ProxyVersion::V2,
+                "192.0.2.1:56324".parse().unwrap(),
+                "198.51.100.2:443".parse().unwrap(),
+            );
+            let mut input = header.to_vec().unwrap();
+            assert_eq!(input[13], 0x11);
+            input[13] = 0x12;
+            assert!(ProxyHeader::decode(Cursor::new(input)).await.is_err());
+
+            let mut input = ProxyHeader::local(ProxyVersion::V2).to_vec().unwrap();
+            input[13] = 0x02;
+            let (_, decoded) = ProxyHeader::decode(Cursor::new(input)).await.unwrap();
+            assert_eq!(decoded, ProxyHeader::local(ProxyVersion::V2));
+        })
+    }
+}
@@ -0,0 +1,156 @@
+mod decode;
+
+pub use decode::*;
+
+use crate::internal::buffer_write::{BufferWrite, BufferWriteState};
+use crate::internal::io_future::{IoFutureState, IoFutureWithOutputState};
+use futures::{AsyncRead, AsyncWrite};
+use std::io;
+use std::io::ErrorKind::InvalidInput;
+use std::net::SocketAddr;
+
+const V2_SIGNATURE: &[u8; 12] = b"\r\n\r\n\0\r\nQUIT\n";
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ProxyVersion {
+    V1,
+    V2,
+}
+
+/// `LOCAL` connections were opened by the proxy itself, e.g. for health checks, and carry no
+/// client addresses.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ProxyCommand {
+    Local,
+    Proxy,
+}
+
+/// Type-length-value extension of a v2 header.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ProxyTlv {
+    pub kind: u8,
+    pub value: Vec<u8>,
+}
+
+/// HAProxy PROXY protocol header, sent by a proxy before the proxied connection's data.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ProxyHeader {
+    pub version: ProxyVersion,
+    pub command: ProxyCommand,
+    /// Source and destination address, if known.
+    pub addresses: Option<(SocketAddr, SocketAddr)>,
+    pub tlvs: Vec<ProxyTlv>,
+}
+
+impl ProxyHeader {
+    pub fn new(version: ProxyVersion, source: SocketAddr, destination: SocketAddr) -> Self {
+        Self {
+            version,
+            command: ProxyCommand::Proxy,
+            addresses: Some((source, destination)),
+            tlvs: Vec::new(),
+        }
+    }
+    pub fn local(version: ProxyVersion) -> Self {
+        Self {
+            version,
+            command: ProxyCommand::Local,
+            addresses: None,
+            tlvs: Vec::new(),
+        }
+    }
+    pub fn source(&self) -> Option<SocketAddr> {
+        self.addresses.map(|(source, _)| source)
+    }
+    pub fn destination(&self) -> Option<SocketAddr> {
+        self.addresses.map(|(_, destination)| destination)
+    }
+    pub fn to_vec(&self) -> io::Result<Vec<u8>> {
+        match self.version {
+            ProxyVersion::V1 => self.to_vec_v1(),
+            ProxyVersion::V2 => self.to_vec_v2(),
+        }
+    }
+    fn to_vec_v1(&self) -> io::Result<Vec<u8>> {
+        if !self.tlvs.is_empty() {
+            return Err(io::Error::new(
+                InvalidInput,
+                "TLVs require PROXY protocol v2",
+            ));
+        }
+        let line = match (self.command, self.addresses) {
+            (ProxyCommand::Proxy, Some((SocketAddr::V4(src), SocketAddr::V4(dst)))) => format!(
+                "PROXY TCP4 {} {} {} {}\r\n",
+                src.ip(),
+                dst.ip(),
+                src.port(),
+                dst.port()
+            ),
+            (ProxyCommand::Proxy, Some((SocketAddr::V6(src), SocketAddr::V6(dst)))) => format!(
+                "PROXY TCP6 {} {} {} {}\r\n",
+                src.ip(),
+                dst.ip(),
+                src.port(),
+                dst.port()
+            ),
+            (ProxyCommand::Proxy, Some(_)) => {
+                return Err(io::Error::new(InvalidInput, "mismatched address families"))
+            }
+            _ => "PROXY UNKNOWN\r\n".to_string(),
+        };
+        Ok(line.into_bytes())
+    }
+    fn to_vec_v2(&self) -> io::Result<Vec<u8>> {
+        let mut payload = Vec::new();
+        let family = match self.addresses {
+            None => 0x00,
+            Some((SocketAddr::V4(src), SocketAddr::V4(dst))) => {
+                payload.extend_from_slice(&src.ip().octets());
+                payload.extend_from_slice(&dst.ip().octets());
+                payload.extend_from_slice(&src.port().to_be_bytes());
+                payload.extend_from_slice(&dst.port().to_be_bytes());
+                0x11
+            }
+            Some((SocketAddr::V6(src), SocketAddr::V6(dst))) => {
+                payload.extend_from_slice(&src.ip().octets());
+                payload.extend_from_slice(&dst.ip().octets());
+                payload.extend_from_slice(&src.port().to_be_bytes());
+                payload.extend_from_slice(&dst.port().to_be_bytes());
+                0x21
+            }
+            Some(_) => return Err(io::Error::new(InvalidInput, "mismatched address families")),
+        };
+        for tlv in &self.tlvs {
+            let len = u16::try_from(tlv.value.len())
+                .map_err(|_| io::Error::new(InvalidInput, "TLV too long"))?;
+            payload.push(tlv.kind);
+            payload.extend_from_slice(&len.to_be_bytes());
+            payload.extend_from_slice(&tlv.value);
+        }
+        let len = u16::try_from(payload.len())
+            .map_err(|_| io::Error::new(InvalidInput, "PROXY header too long"))?;
+        let command = match self.command {
+            ProxyCommand::Local => 0x20,
+            ProxyCommand::Proxy => 0x21,
+        };
+        let mut buffer = V2_SIGNATURE.to_vec();
+        buffer.extend_from_slice(&[command, family]);
+        buffer.extend_from_slice(&len.to_be_bytes());
+        buffer.extend_from_slice(&payload);
+        Ok(buffer)
+    }
+    pub fn encode<IO: AsyncWrite + Unpin>(&self, io: IO) -> BufferWrite<IO> {
+        self.encode_state().into_future(io)
+    }
+    pub fn encode_state(&self) -> BufferWriteState {
+        BufferWriteState::new(self.to_vec())
+    }
+    /// Reads a v1 or v2 header, leaving the transport at the first byte after it. v2 headers of
+    /// proxied datagram connections are rejected.
+    pub fn decode<IO: AsyncRead + Unpin>(io: IO) -> ProxyHeaderDecode<IO> {
+        Self::decode_state().into_future(io)
+    }
+    pub fn decode_state() -> ProxyHeaderDecodeState {
+        ProxyHeaderDecodeState::new()
+    }
+}
@@ -0,0 +1,130 @@
+use crate::internal::buffer_decode::BufferDecodeState;
+use crate::internal::dec_helpers::request_head_parse;
+use crate::internal::io_future::{IoFutureWithOutput, IoFutureWithOutputState};
+use crate::{Error, RequestHead};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use futures::AsyncRead;
+use std::io;
+use std::io::ErrorKind::InvalidData;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// The HTTP/2 connection preface (RFC 9113, section 3.4).
+pub const H2_PREFACE: &[u8] = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
+const H2_PREFACE_HEAD: usize = 18;
+
+/// First message on a server connection, which may switch to HTTP/2.
+#[derive(Debug)]
+pub enum RequestStart {
+    Http1(RequestHead<'static>),
+    /// The client sent the HTTP/2 connection preface, which is returned as consumed bytes.
+    H2Preface {
+        consumed: Vec<u8>,
+    },
+    /// An HTTP/1.1 request asking to upgrade to HTTP/2 over cleartext, with the decoded
+    /// `HTTP2-Settings` payload and the consumed bytes of the request head.
+    H2cUpgrade {
+        head: RequestHead<'static>,
+        settings: Vec<u8>,
+        consumed: Vec<u8>,
+    },
+}
+
+#[allow(clippy::large_enum_variant)]
+enum Parsed {
+    Preface,
+    Head(RequestHead<'static>, Option<(Vec<u8>, Vec<u8>)>),
+}
+
+/// Decodes a request head, or the HTTP/2 connection preface.
+pub struct RequestStartDecodeState {
+    head: BufferDecodeState<Parsed>,
+    preface: Option<usize>,
+    tail: [u8; H2_PREFACE.len() - H2_PREFACE_HEAD],
+}
+
+impl RequestStartDecodeState {
+    pub fn new(max_head: usize, max_headers: usize) -> Self {
+        Self {
+            head: BufferDecodeState::new(max_head, max_headers, &parse),
+            preface: None,
+            tail: Default::default(),
+        }
+    }
+}
+
+impl<IO: AsyncRead + Unpin> IoFutureWithOutputState<IO, RequestStart> for RequestStartDecodeState {
+    fn poll(&mut self, cx: &mut Context<'_>, io: &mut IO) -> Poll<io::Result<RequestStart>> {
+        loop {
+            let completion = match self.preface {
+                Some(completion) => completion,
+                None => match self.head.poll(cx, io) {
+                    Poll::Ready(Ok(Parsed::Preface)) => {
+                        self.preface = Some(0);
+                        continue;
+                    }
+                    Poll::Ready(Ok(Parsed::Head(head, h2c))) => {
+                        return Poll::Ready(Ok(classify(head, h2c)))
+                    }
+                    Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+                    Poll::Pending => return Poll::Pending,
+                },
+            };
+            if completion == self.tail.len() {
+                return match self.tail == H2_PREFACE[H2_PREFACE_HEAD..] {
+                    true => Poll::Ready(Ok(RequestStart::H2Preface {
+                        consumed: H2_PREFACE.to_vec(),
+                    })),
+                    false => {
+                        Poll::Ready(Err(io::Error::new(InvalidData, "invalid HTTP/2 preface")))
+                    }
+                };
+            }
+            match Pin::new(&mut *io).poll_read(cx, &mut self.tail[completion..]) {
+                Poll::Ready(Ok(0)) => return Poll::Ready(Err(io::ErrorKind::UnexpectedEof.into())),
+                Poll::Ready(Ok(n)) => self.preface = Some(completion + n),
+                Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+pub type RequestStartDecode<IO> = IoFutureWithOutput<RequestStartDecodeState, IO, RequestStart>;
+
+fn parse(buffer: &[u8], max_headers: usize) -> Result<Parsed, Error> {
+    if buffer == &H2_PREFACE[..H2_PREFACE_HEAD] {
+        return Ok(Parsed::Preface);
+    }
+    let head = request_head_parse(buffer, max_headers)?;
+    let h2c = h2c_settings(&head).map(|settings| (settings, buffer.to_vec()));
+    Ok(Parsed::Head(head, h2c))
+}
+
+fn classify(head: RequestHead<'static>, h2c: Option<(Vec<u8>, Vec<u8>)>) -> RequestStart {
+    match h2c {
+        Some((settings, consumed)) => RequestStart::H2cUpgrade {
+            head,
+            settings,
+            consumed,
+        },
+        None => RequestStart::Http1(head),
+    }
+}
+
+/// Decoded `HTTP2-Settings` of an h2c upgrade request (RFC 7540, section 3.2).
+fn h2c_settings(head: &RequestHead) -> Option<Vec<u8>> {
+    if !head
+        .upgrade_protocols()
+        .any(|p| p.eq_ignore_ascii_case("h2c"))
+        || !head.has_connection_token("http2-settings")
+    {
+        return None;
+    }
+    let mut settings = head.headers().get_all("http2-settings").iter();
+    match (settings.next(), settings.next()) {
+        (Some(value), None) => URL_SAFE_NO_PAD.decode(value.as_bytes()).ok(),
+        _ => None,
+    }
+}
@@ -1,17 +1,27 @@
+mod h2;
 mod parse;
 #[cfg(test)]
 mod test;
 
+pub use h2::*;
+pub use parse::*;
+
 use crate::internal::buffer_decode::{BufferDecode, BufferDecodeState};
 use crate::internal::buffer_write::{BufferWrite, BufferWriteState};
+use crate::internal::conn_helpers::{
+    has_connection_token, is_persistent, set_persistent, upgrade_protocols,
+};
 use crate::internal::dec_helpers::request_head_parse;
-use crate::internal::enc_helpers::header_encode;
+use crate::internal::enc_helpers::{raw_headers, raw_version};
 use crate::internal::io_future::{IoFutureState, IoFutureWithOutputState};
+use crate::{HeadDecoder, RawRequestHead};
 use futures::{AsyncRead, AsyncWrite};
 use http::request::Parts;
+use http::uri::Authority;
 use http::{HeaderMap, Method, Request, Uri, Version};
 use std::borrow::Cow;
 use std::io;
+use std::io::{Read, Write};
 
 #[derive(Clone, Debug)]
 pub struct RequestHead<'a> {
@@ -46,9 +56,9 @@ impl<'a> RequestHead<'a> {
     pub fn ref_request<B>(request: &'a Request<B>) -> Self {
         Self {
             method: request.method().clone(),
-            uri: Cow::Borrowed(&request.uri()),
+            uri: Cow::Borrowed(request.uri()),
             version: request.version(),
-            headers: Cow::Borrowed(&request.headers()),
+            headers: Cow::Borrowed(request.headers()),
         }
     }
     pub fn to_owned(self) -> RequestHead<'static> {
@@ -60,10 +70,14 @@ impl<'a> RequestHead<'a> {
         }
     }
     pub fn to_vec(&self) -> io::Result<Vec<u8>> {
-        use std::io::Write;
+        let raw = RawRequestHead {
+            method: Cow::Borrowed(self.method.as_str()),
+            target: Cow::Owned(self.uri.to_string()),
+            version: raw_version(self.version)?,
+            headers: raw_headers(&self.headers),
+        };
         let mut buffer = Vec::with_capacity(8192);
-        writeln!(buffer, "{} {} {:?}\r", self.method, &self.uri, self.version)?;
-        header_encode(&mut buffer, &self.headers)?;
+        raw.encode(&mut buffer)?;
         Ok(buffer)
     }
     pub fn encode<IO: AsyncWrite + Unpin>(&self, io: IO) -> BufferWrite<IO> {
@@ -72,11 +86,52 @@ impl<'a> RequestHead<'a> {
     pub fn encode_state(&self) -> BufferWriteState {
         BufferWriteState::new(self.to_vec())
     }
+    /// Blocking counterpart of [`encode`](Self::encode).
+    pub fn encode_blocking<W: Write>(&self, mut io: W) -> io::Result<()> {
+        io.write_all(&self.to_vec()?)?;
+        io.flush()
+    }
+    /// Decodes an HTTP/1 request head. The HTTP/2 connection preface is rejected as an
+    /// unsupported version, servers that may receive it use [`decode_start`](Self::decode_start)
+    /// instead.
     pub fn decode<IO: AsyncRead + Unpin>(io: IO) -> BufferDecode<IO, Self> {
         Self::decode_state().into_future(io)
     }
     pub fn decode_state() -> BufferDecodeState<Self> {
-        BufferDecodeState::new(8192, 128, &request_head_parse)
+        Self::decode_state_with_limits(8192, 128)
+    }
+    pub fn decode_state_with_limits(
+        max_head: usize,
+        max_headers: usize,
+    ) -> BufferDecodeState<Self> {
+        BufferDecodeState::new(max_head, max_headers, &request_head_parse)
+    }
+    /// Blocking counterpart of [`decode`](Self::decode).
+    pub fn decode_blocking<R: Read>(mut io: R) -> io::Result<RequestHead<'static>> {
+        let mut parse = RequestHeadParse::new(8192, 128);
+        parse.read_data(&mut io)?;
+        Ok(parse.try_take_head()?.into())
+    }
+    pub fn decoder() -> HeadDecoder<Self> {
+        Self::decoder_with_limits(8192, 128)
+    }
+    pub fn decoder_with_limits(max_head: usize, max_headers: usize) -> HeadDecoder<Self> {
+        HeadDecoder::new(max_head, max_headers, &request_head_parse)
+    }
+    /// Like [`decode`](Self::decode), but also recognizes the HTTP/2 connection preface and
+    /// h2c upgrade requests. This replaces [`decode`](Self::decode) for the first request on
+    /// a connection that may switch to HTTP/2.
+    pub fn decode_start<IO: AsyncRead + Unpin>(io: IO) -> RequestStartDecode<IO> {
+        Self::decode_start_state().into_future(io)
+    }
+    pub fn decode_start_state() -> RequestStartDecodeState {
+        Self::decode_start_state_with_limits(8192, 128)
+    }
+    pub fn decode_start_state_with_limits(
+        max_head: usize,
+        max_headers: usize,
+    ) -> RequestStartDecodeState {
+        RequestStartDecodeState::new(max_head, max_headers)
     }
     pub fn method(&self) -> Method {
         self.method.clone()
@@ -102,6 +157,29 @@ impl<'a> RequestHead<'a> {
     pub fn headers_mut(&mut self) -> &mut HeaderMap {
         self.headers.to_mut()
     }
+    /// Whether the `Connection` header lists `token`, ignoring case.
+    pub fn has_connection_token(&self, token: &str) -> bool {
+        has_connection_token(&self.headers, token)
+    }
+    /// Whether the client asks for the connection to persist after this exchange.
+    pub fn is_persistent(&self) -> bool {
+        is_persistent(self.version, &self.headers)
+    }
+    /// Sets the `close` or, for HTTP/1.0, `keep-alive` connection option.
+    pub fn set_persistent(&mut self, persistent: bool) {
+        set_persistent(self.headers.to_mut(), persistent, self.version)
+    }
+    /// Target of a `CONNECT` request, which must be in authority-form (`host:port`).
+    pub fn connect_authority(&self) -> Option<&Authority> {
+        match (&self.method, self.uri.scheme(), self.uri.path_and_query()) {
+            (&Method::CONNECT, None, None) => self.uri.authority(),
+            _ => None,
+        }
+    }
+    /// Protocols the client offers to upgrade the connection to, in order of preference.
+    pub fn upgrade_protocols(&self) -> impl Iterator<Item = &str> {
+        upgrade_protocols(self.version, &self.headers)
+    }
 }
 
 impl From<Parts> for RequestHead<'static> {
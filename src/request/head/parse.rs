@@ -1,66 +1,39 @@
+use crate::{HeadDecoder, RequestHead};
 use http::request::Parts;
-use http::{Method, Request, Uri, Version};
 use std::io;
 use std::io::ErrorKind::InvalidData;
 use std::io::Read;
 
-use crate::internal::parse_helper::copy_parsed_headers;
-use crate::internal::terminator::TerminatorOverlap;
-
-pub struct RequestHeadParse<'a> {
-    buffer: Vec<u8>,
-    terminator: TerminatorOverlap<'a>,
-    max_headers: usize,
+/// Blocking request head reader on top of [`HeadDecoder`], which never reads past the end of
+/// the head, so the body can be read from the same reader afterwards.
+pub struct RequestHeadParse {
+    decoder: HeadDecoder<RequestHead<'static>>,
+    head: Option<RequestHead<'static>>,
+    len: usize,
 }
 
-impl<'a> RequestHeadParse<'a> {
-    const END: &'a [u8] = b"\r\n\r\n";
+impl RequestHeadParse {
     pub fn new(max_buffer: usize, max_headers: usize) -> Self {
         Self {
-            buffer: Vec::with_capacity(max_buffer),
-            terminator: TerminatorOverlap::new(Self::END),
-            max_headers,
+            decoder: RequestHead::decoder_with_limits(max_buffer, max_headers),
+            head: None,
+            len: 0,
         }
     }
-    pub fn read_data<T: Read>(&mut self, rd: &mut T) -> Result<usize, std::io::Error> {
-        let mut chunks = [0u8; Self::END.len()];
-        while !self.terminator.done() {
-            let chunks = self.terminator.max_read_buf(&mut chunks);
-            if self.buffer.capacity() - self.buffer.len() < chunks.len() {
-                return Err(std::io::ErrorKind::OutOfMemory.into());
-            }
-            rd.read_exact(chunks)?;
-            self.terminator.process(&chunks);
-            self.buffer.extend_from_slice(chunks);
+    /// Reads the head from `rd`, returning its length in bytes.
+    pub fn read_data<T: Read>(&mut self, rd: &mut T) -> io::Result<usize> {
+        if self.head.is_none() {
+            let mut rd = rd.take(u64::MAX);
+            self.head = Some(self.decoder.read_from(&mut rd)?);
+            self.len = (u64::MAX - rd.limit()) as usize;
         }
-        Ok(self.buffer.len())
+        Ok(self.len)
     }
+    /// Takes the head read by [`read_data`](Self::read_data).
     pub fn try_take_head(&mut self) -> io::Result<Parts> {
-        let mut headers = vec![httparse::EMPTY_HEADER; self.max_headers];
-        let mut parsed_request = httparse::Request::new(&mut headers);
-        if parsed_request
-            .parse(self.buffer.as_ref())
-            .map_err(|err| io::Error::new(InvalidData, err.to_string()))?
-            .is_partial()
-        {
-            return Err(io::Error::new(InvalidData, "malformed HTTP head"));
-        }
-        if parsed_request.version != Some(1) {
-            return Err(io::Error::new(InvalidData, "unsupported HTTP version"));
+        match self.head.take() {
+            Some(head) => Ok(head.into()),
+            None => Err(io::Error::new(InvalidData, "incomplete HTTP head")),
         }
-        let method = Method::from_bytes(parsed_request.method.unwrap_or("").as_bytes())
-            .map_err(|err| io::Error::new(InvalidData, err.to_string()))?;
-        let uri = parsed_request
-            .path
-            .unwrap_or("")
-            .parse::<Uri>()
-            .map_err(|_| io::Error::new(InvalidData, "invalid uri"))?;
-        let mut request = Request::new(());
-        *request.method_mut() = method;
-        *request.uri_mut() = uri;
-        *request.version_mut() = Version::HTTP_11;
-        let headers = request.headers_mut();
-        copy_parsed_headers(headers, parsed_request.headers)?;
-        Ok(request.into_parts().0)
     }
 }
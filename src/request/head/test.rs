@@ -1,7 +1,9 @@
-use crate::request::head::RequestHead;
+use crate::internal::io_future::IoFutureWithOutputState;
+use crate::request::head::{RequestHead, RequestStart, H2_PREFACE};
 use futures::executor::block_on;
 use futures::io::Cursor;
-use http::{Method, Version};
+use http::{HeaderMap, HeaderValue, Method, Uri, Version};
+use std::borrow::Cow;
 
 use super::parse::RequestHeadParse;
 
@@ -33,12 +35,118 @@ fn test() {
     })
 }
 
+#[test]
+fn test_http10() {
+    block_on(async {
+        const INPUT: &[u8] = b"GET / HTTP/1.0\r\nhost: www.example.com\r\n\r\n";
+        let head = RequestHead::decode(Cursor::new(INPUT)).await.unwrap().1;
+        assert_eq!(head.version(), Version::HTTP_10);
+
+        let mut transport = Cursor::new(Vec::new());
+        head.encode(&mut transport).await.unwrap();
+        assert_eq!(transport.into_inner(), INPUT);
+    })
+}
+
+#[test]
+fn test_blocking() {
+    let mut input = [INPUT, b"body"].concat();
+    let mut reader = &input[..];
+    let head = RequestHead::decode_blocking(&mut reader).unwrap();
+    block_on(check(&head));
+    assert_eq!(reader, b"body");
+
+    input.clear();
+    head.encode_blocking(&mut input).unwrap();
+    assert_eq!(input, INPUT);
+}
+
+#[test]
+fn test_persistence() {
+    let mut head = RequestHead::new(
+        Method::GET,
+        Cow::Owned(Uri::from_static("/")),
+        Version::HTTP_10,
+        Cow::Owned(HeaderMap::new()),
+    );
+    assert!(!head.is_persistent());
+    head.set_persistent(true);
+    assert_eq!(head.headers()["connection"], "keep-alive");
+    assert!(head.is_persistent());
+
+    *head.version_mut() = Version::HTTP_11;
+    head.headers_mut().insert(
+        "connection",
+        HeaderValue::from_static("Upgrade, Keep-Alive"),
+    );
+    assert!(head.is_persistent());
+    assert!(head.has_connection_token("upgrade"));
+    head.set_persistent(false);
+    assert_eq!(head.headers()["connection"], "Upgrade, close");
+    assert!(!head.is_persistent());
+    head.set_persistent(true);
+    assert_eq!(head.headers()["connection"], "Upgrade");
+}
+
+#[test]
+fn test_h2_detection() {
+    block_on(async {
+        let input = [H2_PREFACE, b"\x00\x00\x00\x04\x00\x00\x00\x00\x00"].concat();
+        let (io, start) = RequestHead::decode_start(Cursor::new(input)).await.unwrap();
+        match start {
+            RequestStart::H2Preface { consumed } => assert_eq!(consumed, H2_PREFACE),
+            start => panic!("unexpected {:?}", start),
+        }
+        assert_eq!(io.position(), H2_PREFACE.len() as u64);
+
+        let input = b"PRI * HTTP/2.0\r\n\r\nXX\r\n\r\n";
+        assert!(RequestHead::decode_start(Cursor::new(input)).await.is_err());
+
+        let input = b"GET / HTTP/1.1\r\nhost: a\r\nconnection: Upgrade, HTTP2-Settings\r\nupgrade: h2c\r\nhttp2-settings: AAMAAABkAAQCAAAAAAIAAAAA\r\n\r\n";
+        match RequestHead::decode_start(Cursor::new(input))
+            .await
+            .unwrap()
+            .1
+        {
+            RequestStart::H2cUpgrade {
+                head,
+                settings,
+                consumed,
+            } => {
+                assert_eq!(head.uri(), "/");
+                assert_eq!(settings.len(), 18);
+                assert_eq!(consumed, input);
+            }
+            start => panic!("unexpected {:?}", start),
+        }
+        let input = b"GET / HTTP/1.1\r\nconnection: upgrade, http2-settings\r\nupgrade: H2C\r\nhttp2-settings: AAMAAABkAAQCAAAAAAIAAAAA\r\n\r\n";
+        let (_, start) = RequestHead::decode_start(Cursor::new(input)).await.unwrap();
+        assert!(matches!(start, RequestStart::H2cUpgrade { .. }));
+
+        match RequestHead::decode_start(Cursor::new(INPUT))
+            .await
+            .unwrap()
+            .1
+        {
+            RequestStart::Http1(head) => check(&head).await,
+            start => panic!("unexpected {:?}", start),
+        }
+
+        let state = RequestHead::decode_start_state_with_limits(16, 128);
+        assert!(state.into_future(Cursor::new(INPUT)).await.is_err());
+        let state = RequestHead::decode_start_state_with_limits(24, 128);
+        let (_, start) = state.into_future(Cursor::new(H2_PREFACE)).await.unwrap();
+        assert!(matches!(start, RequestStart::H2Preface { .. }));
+        assert!(RequestHead::decode(Cursor::new(H2_PREFACE)).await.is_err());
+    })
+}
+
 #[test]
 fn test_request_head_parse() {
     let mut parser = RequestHeadParse::new(8096, 10);
     let mut input = INPUT;
     let size = parser.read_data(&mut input).unwrap();
-    println!("{}", size);
+    assert_eq!(size, INPUT.len());
     let part = parser.try_take_head().unwrap();
     let head = RequestHead::from(part);
     block_on(check(&head));
@@ -0,0 +1,55 @@
+use crate::internal::buffer_decode::BufferDecodeState;
+use crate::internal::io_future::{IoFutureWithOutput, IoFutureWithOutputState};
+use crate::ResponseHead;
+use futures::AsyncRead;
+use http::StatusCode;
+use std::io;
+use std::task::{Context, Poll};
+
+/// Decodes response heads until the final one, passing interim (1xx) heads to a callback.
+/// `101 Switching Protocols` is final, since the connection changes protocol after it.
+pub struct FinalResponseDecodeState<F: FnMut(ResponseHead<'static>) + Unpin> {
+    head: BufferDecodeState<ResponseHead<'static>>,
+    max_head: usize,
+    max_headers: usize,
+    on_interim: F,
+}
+
+impl<F: FnMut(ResponseHead<'static>) + Unpin> FinalResponseDecodeState<F> {
+    pub fn new(max_head: usize, max_headers: usize, on_interim: F) -> Self {
+        Self {
+            head: ResponseHead::decode_state_with_limits(max_head, max_headers),
+            max_head,
+            max_headers,
+            on_interim,
+        }
+    }
+}
+
+impl<IO: AsyncRead + Unpin, F: FnMut(ResponseHead<'static>) + Unpin>
+    IoFutureWithOutputState<IO, ResponseHead<'static>> for FinalResponseDecodeState<F>
+{
+    fn poll(
+        &mut self,
+        cx: &mut Context<'_>,
+        io: &mut IO,
+    ) -> Poll<io::Result<ResponseHead<'static>>> {
+        loop {
+            match self.head.poll(cx, io) {
+                Poll::Ready(Ok(head)) if is_interim(head.status()) => {
+                    self.head =
+                        ResponseHead::decode_state_with_limits(self.max_head, self.max_headers);
+                    (self.on_interim)(head)
+                }
+                p => return p,
+            }
+        }
+    }
+}
+
+pub type FinalResponseDecode<IO, F> =
+    IoFutureWithOutput<FinalResponseDecodeState<F>, IO, ResponseHead<'static>>;
+
+pub(crate) fn is_interim(status: StatusCode) -> bool {
+    status.is_informational() && status != StatusCode::SWITCHING_PROTOCOLS
+}
@@ -1,17 +1,25 @@
+pub(crate) mod interim;
 mod parse;
 #[cfg(test)]
 mod test;
 
+pub use interim::*;
+pub use parse::*;
+
 use crate::internal::buffer_decode::{BufferDecode, BufferDecodeState};
 use crate::internal::buffer_write::{BufferWrite, BufferWriteState};
+use crate::internal::conn_helpers::{has_connection_token, is_persistent, set_persistent};
 use crate::internal::dec_helpers::response_head_parse;
-use crate::internal::enc_helpers::{header_encode, status_line_encode};
+use crate::internal::enc_helpers::{raw_headers, raw_version};
 use crate::internal::io_future::{IoFutureState, IoFutureWithOutputState};
+use crate::RequestHead;
+use crate::{HeadDecoder, RawResponseHead};
 use futures::{AsyncRead, AsyncWrite};
 use http::response::Parts;
 use http::{HeaderMap, Response, StatusCode, Version};
 use std::borrow::Cow;
 use std::io;
+use std::io::{Read, Write};
 
 #[derive(Clone, Debug)]
 pub struct ResponseHead<'a> {
@@ -25,7 +33,7 @@ impl<'a> ResponseHead<'a> {
         Self {
             status,
             version,
-            headers: headers.into(),
+            headers,
         }
     }
     pub fn ref_parts(parts: &'a Parts) -> Self {
@@ -39,7 +47,7 @@ impl<'a> ResponseHead<'a> {
         Self {
             status: response.status(),
             version: response.version(),
-            headers: Cow::Borrowed(&response.headers()),
+            headers: Cow::Borrowed(response.headers()),
         }
     }
     pub fn to_owned(self) -> ResponseHead<'static> {
@@ -50,9 +58,14 @@ impl<'a> ResponseHead<'a> {
         }
     }
     pub fn to_vec(&self) -> io::Result<Vec<u8>> {
+        let raw = RawResponseHead {
+            version: raw_version(self.version)?,
+            status: self.status.as_u16(),
+            reason: Cow::Borrowed(self.status.canonical_reason().unwrap_or("")),
+            headers: raw_headers(&self.headers),
+        };
         let mut buffer = Vec::with_capacity(8192);
-        status_line_encode(&mut buffer, &self.version, &self.status)?;
-        header_encode(&mut buffer, &self.headers)?;
+        raw.encode(&mut buffer)?;
         Ok(buffer)
     }
     pub fn encode<IO: AsyncWrite + Unpin>(&self, io: IO) -> BufferWrite<IO> {
@@ -61,11 +74,54 @@ impl<'a> ResponseHead<'a> {
     pub fn encode_state(&self) -> BufferWriteState {
         BufferWriteState::new(self.to_vec())
     }
+    /// Blocking counterpart of [`encode`](Self::encode).
+    pub fn encode_blocking<W: Write>(&self, mut io: W) -> io::Result<()> {
+        io.write_all(&self.to_vec()?)?;
+        io.flush()
+    }
     pub fn decode<IO: AsyncRead + Unpin>(io: IO) -> BufferDecode<IO, Self> {
         Self::decode_state().into_future(io)
     }
     pub fn decode_state() -> BufferDecodeState<Self> {
-        BufferDecodeState::new(8192, 128, &response_head_parse)
+        Self::decode_state_with_limits(8192, 128)
+    }
+    pub fn decode_state_with_limits(
+        max_head: usize,
+        max_headers: usize,
+    ) -> BufferDecodeState<Self> {
+        BufferDecodeState::new(max_head, max_headers, &response_head_parse)
+    }
+    /// Blocking counterpart of [`decode`](Self::decode).
+    pub fn decode_blocking<R: Read>(mut io: R) -> io::Result<ResponseHead<'static>> {
+        let mut parse = ResponseHeadParse::new(8192, 128);
+        parse.read_data(&mut io)?;
+        Ok(parse.try_take_head()?.into())
+    }
+    pub fn decoder() -> HeadDecoder<Self> {
+        Self::decoder_with_limits(8192, 128)
+    }
+    pub fn decoder_with_limits(max_head: usize, max_headers: usize) -> HeadDecoder<Self> {
+        HeadDecoder::new(max_head, max_headers, &response_head_parse)
+    }
+    /// Decodes the final response head, passing interim (1xx) heads to `on_interim`.
+    pub fn decode_final<IO: AsyncRead + Unpin, F: FnMut(ResponseHead<'static>) + Unpin>(
+        io: IO,
+        on_interim: F,
+    ) -> FinalResponseDecode<IO, F> {
+        FinalResponseDecodeState::new(8192, 128, on_interim).into_future(io)
+    }
+    /// Blocking counterpart of [`decode_final`](Self::decode_final).
+    pub fn decode_final_blocking<R: Read>(
+        mut io: R,
+        mut on_interim: impl FnMut(ResponseHead<'static>),
+    ) -> io::Result<ResponseHead<'static>> {
+        let mut decoder = ResponseHead::decoder();
+        loop {
+            match decoder.read_from(&mut io)? {
+                head if is_interim(head.status()) => on_interim(head),
+                head => return Ok(head),
+            }
+        }
     }
     pub fn status(&self) -> StatusCode {
         self.status
@@ -85,6 +141,20 @@ impl<'a> ResponseHead<'a> {
     pub fn headers_mut(&mut self) -> &mut HeaderMap {
         self.headers.to_mut()
     }
+    /// Whether the `Connection` header lists `token`, ignoring case.
+    pub fn has_connection_token(&self, token: &str) -> bool {
+        has_connection_token(&self.headers, token)
+    }
+    /// Whether the connection persists after this response to `request`.
+    ///
+    /// Framing is not taken into account, a close-delimited body always ends the connection.
+    pub fn is_persistent(&self, request: &RequestHead) -> bool {
+        request.is_persistent() && is_persistent(self.version.min(request.version()), &self.headers)
+    }
+    /// Sets the `close` or, for HTTP/1.0 peers, `keep-alive` connection option.
+    pub fn set_persistent(&mut self, persistent: bool, peer: Version) {
+        set_persistent(self.headers.to_mut(), persistent, peer)
+    }
 }
 
 impl From<Parts> for ResponseHead<'static> {
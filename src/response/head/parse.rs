@@ -1,56 +1,39 @@
-use std::io::{self, ErrorKind::InvalidData, Read};
+use crate::{HeadDecoder, ResponseHead};
+use http::response::Parts;
+use std::io;
+use std::io::ErrorKind::InvalidData;
+use std::io::Read;
 
-use http::{response::Parts, Response, StatusCode, Version};
-
-use crate::internal::{dec_helpers::copy_parsed_headers, terminator::TerminatorOverlap};
-
-pub struct ResponseHeadParse<'a> {
-    buffer: Vec<u8>,
-    terminator: TerminatorOverlap<'a>,
-    max_headers: usize,
+/// Blocking response head reader on top of [`HeadDecoder`], which never reads past the end of
+/// the head, so the body can be read from the same reader afterwards.
+pub struct ResponseHeadParse {
+    decoder: HeadDecoder<ResponseHead<'static>>,
+    head: Option<ResponseHead<'static>>,
+    len: usize,
 }
 
-impl<'a> ResponseHeadParse<'a> {
-    const END: &'a [u8] = b"\r\n\r\n";
+impl ResponseHeadParse {
     pub fn new(max_buffer: usize, max_headers: usize) -> Self {
         Self {
-            buffer: Vec::with_capacity(max_buffer),
-            terminator: TerminatorOverlap::new(Self::END),
-            max_headers,
+            decoder: ResponseHead::decoder_with_limits(max_buffer, max_headers),
+            head: None,
+            len: 0,
         }
     }
-    pub fn read_data<T: Read>(&mut self, rd: &mut T) -> Result<usize, std::io::Error> {
-        let mut chunks = [0u8; Self::END.len()];
-        while !self.terminator.done() {
-            let chunks = self.terminator.max_read_buf(&mut chunks);
-            if self.buffer.capacity() - self.buffer.len() < chunks.len() {
-                return Err(std::io::ErrorKind::OutOfMemory.into());
-            }
-            rd.read_exact(chunks)?;
-            self.terminator.process(&chunks);
-            self.buffer.extend_from_slice(chunks);
+    /// Reads the head from `rd`, returning its length in bytes.
+    pub fn read_data<T: Read>(&mut self, rd: &mut T) -> io::Result<usize> {
+        if self.head.is_none() {
+            let mut rd = rd.take(u64::MAX);
+            self.head = Some(self.decoder.read_from(&mut rd)?);
+            self.len = (u64::MAX - rd.limit()) as usize;
         }
-        Ok(self.buffer.len())
+        Ok(self.len)
     }
+    /// Takes the head read by [`read_data`](Self::read_data).
     pub fn try_take_head(&mut self) -> io::Result<Parts> {
-        let mut headers = vec![httparse::EMPTY_HEADER; self.max_headers];
-        let mut parsed_response = httparse::Response::new(&mut headers);
-        if parsed_response
-            .parse(self.buffer.as_slice())
-            .map_err(|err| io::Error::new(InvalidData, err.to_string()))?
-            .is_partial()
-        {
-            return Err(io::Error::new(InvalidData, "malformed HTTP head"));
-        }
-        if parsed_response.version != Some(1) {
-            return Err(io::Error::new(InvalidData, "unsupported HTTP version"));
+        match self.head.take() {
+            Some(head) => Ok(head.into()),
+            None => Err(io::Error::new(InvalidData, "incomplete HTTP head")),
         }
-        let mut response = Response::new(());
-        *response.version_mut() = Version::HTTP_11;
-        *response.status_mut() = StatusCode::from_u16(parsed_response.code.unwrap())
-            .map_err(|_| io::Error::new(InvalidData, "invalid status code"))?;
-        let headers = response.headers_mut();
-        copy_parsed_headers(headers, parsed_response.headers)?;
-        Ok(response.into_parts().0)
     }
 }
@@ -1,7 +1,9 @@
 use crate::response::head::{parse::ResponseHeadParse, ResponseHead};
+use crate::RequestHead;
 use futures::executor::block_on;
 use futures::io::Cursor;
-use http::{StatusCode, Version};
+use http::{HeaderMap, HeaderValue, Method, Request, StatusCode, Version};
+use std::borrow::Cow;
 
 const INPUT: &[u8] = b"HTTP/1.1 201 Created\r\nconnection: close\r\n\r\n";
 
@@ -29,12 +31,74 @@ fn test() {
     })
 }
 
+#[test]
+fn test_decode_final() {
+    block_on(async {
+        const INPUT: &[u8] = b"\
+HTTP/1.1 100 Continue\r\n\r\n\
+HTTP/1.1 103 Early Hints\r\nlink: </style.css>; rel=preload\r\n\r\n\
+HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n\
+HTTP/1.1 101 Switching Protocols\r\n\r\n";
+        let mut interim = Vec::new();
+        let (mut io, head) =
+            ResponseHead::decode_final(Cursor::new(INPUT), |head| interim.push(head))
+                .await
+                .unwrap();
+        assert_eq!(head.status(), StatusCode::OK);
+        let statuses: Vec<_> = interim.iter().map(|head| head.status()).collect();
+        assert_eq!(statuses, [StatusCode::CONTINUE, StatusCode::EARLY_HINTS]);
+        assert!(interim[1].headers().contains_key("link"));
+
+        let (_, head) = ResponseHead::decode_final(&mut io, |_| panic!())
+            .await
+            .unwrap();
+        assert_eq!(head.status(), StatusCode::SWITCHING_PROTOCOLS);
+    })
+}
+
+#[test]
+fn test_blocking() {
+    let input = [b"HTTP/1.1 100 Continue\r\n\r\n", INPUT].concat();
+    let mut interim = Vec::new();
+    let head = ResponseHead::decode_final_blocking(&input[..], |head| interim.push(head.status()))
+        .unwrap();
+    block_on(check(&head));
+    assert_eq!(interim, [StatusCode::CONTINUE]);
+
+    let mut output = Vec::new();
+    head.encode_blocking(&mut output).unwrap();
+    assert_eq!(output, INPUT);
+}
+
+#[test]
+fn test_persistence() {
+    let mut request = Request::new(());
+    *request.method_mut() = Method::GET;
+    *request.version_mut() = Version::HTTP_10;
+    request
+        .headers_mut()
+        .insert("connection", HeaderValue::from_static("keep-alive"));
+    let request = RequestHead::from(request);
+
+    let mut head = ResponseHead::new(
+        StatusCode::OK,
+        Version::HTTP_11,
+        Cow::Owned(HeaderMap::new()),
+    );
+    assert!(!head.is_persistent(&request));
+    head.set_persistent(true, request.version());
+    assert!(head.is_persistent(&request));
+    head.set_persistent(false, request.version());
+    assert_eq!(head.headers()["connection"], "close");
+    assert!(!head.is_persistent(&request));
+}
+
 #[test]
 fn test_request_head_parse() {
     let mut parser = ResponseHeadParse::new(8096, 10);
     let mut input = INPUT;
     let size = parser.read_data(&mut input).unwrap();
-    println!("{}", size);
+    assert_eq!(size, INPUT.len());
     let part = parser.try_take_head().unwrap();
     let head = ResponseHead::from(part);
     block_on(check(&head));
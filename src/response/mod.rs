@@ -1,3 +1,3 @@
-mod head;
+pub(crate) mod head;
 
 pub use head::*;
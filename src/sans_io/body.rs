@@ -0,0 +1,297 @@
+use crate::Error;
+use alloc::vec::Vec;
+use core::cmp::min;
+#[cfg(feature = "std")]
+use {
+    crate::body::common::length_from_headers,
+    crate::body::framing::allows_content,
+    crate::internal::dec_helpers::copy_parsed_headers,
+    crate::ResponseHead,
+    http::header::{CONTENT_LENGTH, TRANSFER_ENCODING},
+    http::{HeaderMap, Method},
+};
+
+/// Output of a push-based decoder, borrowing body data from the pushed input.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum BodyEvent<'a> {
+    Data(&'a [u8]),
+    /// Trailer fields of a chunked body, before [`BodyEvent::End`].
+    Trailers(RawTrailers),
+    End,
+}
+
+/// Validated trailer section of a chunked body, as received.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RawTrailers(Vec<u8>);
+
+impl RawTrailers {
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+    #[cfg(feature = "std")]
+    pub fn to_headers(&self) -> Result<HeaderMap, Error> {
+        let mut headers = [httparse::EMPTY_HEADER; 64];
+        let mut trailers = HeaderMap::new();
+        if let Ok(httparse::Status::Complete((_, parsed))) =
+            httparse::parse_headers(&self.0, &mut headers)
+        {
+            copy_parsed_headers(&mut trailers, parsed)?;
+        }
+        Ok(trailers)
+    }
+}
+
+/// Sans-IO body decoder. Input is pushed as byte slices of any size and decoded into
+/// [`BodyEvent`]s, together with the number of input bytes consumed.
+#[derive(Clone, Debug)]
+pub struct BodyDecoder {
+    state: State,
+    remaining: u64,
+    trailer: Vec<u8>,
+    max_trailer: usize,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum State {
+    FixedLength,
+    UntilClose,
+    Chunked(ChunkState),
+    Failed,
+    Done,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum ChunkState {
+    Size,
+    Extension,
+    SizeLF,
+    Content,
+    ContentCR,
+    ContentLF,
+    Trailer,
+}
+
+impl BodyDecoder {
+    /// Fixed-length body, or chunked body if `length` is `None`.
+    pub fn new(length: Option<u64>) -> Self {
+        let (state, remaining) = match length {
+            Some(0) => (State::Done, 0),
+            Some(length) => (State::FixedLength, length),
+            None => (State::Chunked(ChunkState::Size), 0),
+        };
+        Self {
+            state,
+            remaining,
+            trailer: Vec::new(),
+            max_trailer: 8192,
+        }
+    }
+    #[cfg(feature = "std")]
+    pub fn from_headers(headers: &HeaderMap) -> anyhow::Result<Self> {
+        Ok(Self::new(length_from_headers(headers)?))
+    }
+    /// Body decoder for a response, following the message length rules of RFC 9112 section 6.3.
+    /// Responses without framing headers are delimited by the connection closing.
+    #[cfg(feature = "std")]
+    pub fn from_response_head(
+        head: &ResponseHead,
+        request_method: &Method,
+    ) -> anyhow::Result<Self> {
+        let headers = head.headers();
+        let tunnel = request_method == Method::CONNECT && head.status().is_success();
+        if request_method == Method::HEAD || tunnel || !allows_content(head.status()) {
+            Ok(Self::new(Some(0)))
+        } else if headers.contains_key(TRANSFER_ENCODING) || headers.contains_key(CONTENT_LENGTH) {
+            Self::from_headers(headers)
+        } else {
+            Ok(Self::until_close())
+        }
+    }
+    /// Body delimited by the end of input, see [`finish`](Self::finish).
+    pub fn until_close() -> Self {
+        let mut decoder = Self::new(Some(0));
+        decoder.state = State::UntilClose;
+        decoder
+    }
+    pub fn is_done(&self) -> bool {
+        self.state == State::Done
+    }
+    pub fn is_close_delimited(&self) -> bool {
+        self.state == State::UntilClose
+    }
+    /// Number of body bytes left, if known upfront.
+    pub fn remaining(&self) -> Option<u64> {
+        match self.state {
+            State::FixedLength => Some(self.remaining),
+            State::Done => Some(0),
+            _ => None,
+        }
+    }
+    /// Maximum number of bytes that can be pushed next without input being left over, which
+    /// allows reading from a transport without reading past the end of the body.
+    pub fn read_hint(&self) -> usize {
+        match self.state {
+            State::FixedLength | State::Chunked(ChunkState::Content) => {
+                min(self.remaining, usize::MAX as u64) as usize
+            }
+            State::UntilClose => usize::MAX,
+            State::Chunked(_) => 1,
+            State::Failed | State::Done => 0,
+        }
+    }
+    /// Decodes input until an event occurs, returning the number of bytes consumed. Returns no
+    /// event if all input was consumed without one. Once done, [`BodyEvent::End`] is returned.
+    pub fn push<'a>(&mut self, input: &'a [u8]) -> Result<(usize, Option<BodyEvent<'a>>), Error> {
+        let result = self.decode(input);
+        if result.is_err() {
+            self.state = State::Failed;
+        }
+        result
+    }
+    /// Signals the end of input, which completes bodies delimited by the connection closing.
+    pub fn finish(&mut self) -> Result<(), Error> {
+        match self.state {
+            State::UntilClose | State::Done => {
+                self.state = State::Done;
+                Ok(())
+            }
+            _ => {
+                self.state = State::Failed;
+                Err(Error::UnexpectedEof)
+            }
+        }
+    }
+    #[cfg(feature = "std")]
+    pub(crate) fn fail(&mut self) {
+        self.state = State::Failed;
+    }
+    fn decode<'a>(&mut self, input: &'a [u8]) -> Result<(usize, Option<BodyEvent<'a>>), Error> {
+        let mut offset = 0;
+        loop {
+            let rest = &input[offset..];
+            let chunk_state = match self.state {
+                State::Failed => return Err(Error::Failed),
+                State::Done => return Ok((offset, Some(BodyEvent::End))),
+                _ if rest.is_empty() => return Ok((offset, None)),
+                State::UntilClose => return Ok((input.len(), Some(BodyEvent::Data(rest)))),
+                State::FixedLength | State::Chunked(ChunkState::Content) => {
+                    let n = min(self.remaining, rest.len() as u64) as usize;
+                    self.remaining -= n as u64;
+                    if self.remaining == 0 {
+                        self.state = match self.state {
+                            State::FixedLength => State::Done,
+                            _ => State::Chunked(ChunkState::ContentCR),
+                        };
+                    }
+                    return Ok((offset + n, Some(BodyEvent::Data(&rest[..n]))));
+                }
+                State::Chunked(chunk_state) => chunk_state,
+            };
+            let byte = rest[0];
+            offset += 1;
+            self.state = State::Chunked(match (chunk_state, byte) {
+                (ChunkState::Size, b'\r') => ChunkState::SizeLF,
+                (ChunkState::Size, b';') => ChunkState::Extension,
+                (ChunkState::Size, digit) => {
+                    let digit = (digit as char)
+                        .to_digit(16)
+                        .ok_or(Error::InvalidBody("invalid chunk size"))?;
+                    self.remaining = self
+                        .remaining
+                        .checked_mul(16)
+                        .ok_or(Error::InvalidBody("chunk size too large"))?
+                        + digit as u64;
+                    ChunkState::Size
+                }
+                (ChunkState::Extension, b'\r') => ChunkState::SizeLF,
+                (ChunkState::Extension, _) => ChunkState::Extension,
+                (ChunkState::SizeLF, b'\n') => match self.remaining {
+                    0 => ChunkState::Trailer,
+                    _ => ChunkState::Content,
+                },
+                (ChunkState::ContentCR, b'\r') => ChunkState::ContentLF,
+                (ChunkState::ContentLF, b'\n') => ChunkState::Size,
+                (ChunkState::Trailer, byte) => {
+                    self.trailer.push(byte);
+                    if self.trailer == b"\r\n" {
+                        self.state = State::Done;
+                        return Ok((offset, Some(BodyEvent::End)));
+                    }
+                    if self.trailer.ends_with(b"\r\n\r\n") {
+                        self.state = State::Done;
+                        let trailers = parse_trailers(&self.trailer)?;
+                        return Ok((offset, Some(BodyEvent::Trailers(trailers))));
+                    }
+                    if self.trailer.len() > self.max_trailer {
+                        return Err(Error::InvalidBody("trailer section too long"));
+                    }
+                    ChunkState::Trailer
+                }
+                _ => return Err(Error::InvalidBody("malformed chunked body")),
+            });
+        }
+    }
+}
+
+fn parse_trailers(buffer: &[u8]) -> Result<RawTrailers, Error> {
+    let mut headers = [httparse::EMPTY_HEADER; 64];
+    match httparse::parse_headers(buffer, &mut headers) {
+        Ok(httparse::Status::Complete(_)) => Ok(RawTrailers(buffer.to_vec())),
+        _ => Err(Error::InvalidBody("malformed trailer section")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{BodyDecoder, BodyEvent};
+    use alloc::vec::Vec;
+
+    /// Pushes `input` in pieces of `step` bytes and collects data, trailers and consumed bytes.
+    fn decode(decoder: &mut BodyDecoder, input: &[u8], step: usize) -> (Vec<u8>, usize, bool) {
+        let (mut data, mut consumed, mut trailers) = (Vec::new(), 0, false);
+        loop {
+            let end = (consumed + step).min(input.len());
+            let (n, event) = decoder.push(&input[consumed..end]).unwrap();
+            consumed += n;
+            match event {
+                Some(BodyEvent::Data(d)) => data.extend_from_slice(d),
+                Some(BodyEvent::Trailers(t)) => {
+                    trailers = t.as_bytes() == b"expires: never\r\n\r\n"
+                }
+                Some(BodyEvent::End) => return (data, consumed, trailers),
+                None => assert_eq!(consumed, end),
+            }
+        }
+    }
+
+    #[test]
+    fn chunked() {
+        let input = b"5;ext=1\r\nhello\r\n7\r\n world!\r\n0\r\nexpires: never\r\n\r\nnext";
+        for step in [1, 3, input.len()] {
+            let mut decoder = BodyDecoder::new(None);
+            let (data, consumed, trailers) = decode(&mut decoder, input, step);
+            assert_eq!(data, b"hello world!");
+            assert_eq!(consumed, input.len() - 4);
+            assert!(trailers);
+            assert!(decoder.is_done());
+        }
+        let mut decoder = BodyDecoder::new(None);
+        let (data, consumed, trailers) = decode(&mut decoder, b"0\r\n\r\n", 2);
+        assert_eq!((data.len(), consumed, trailers), (0, 5, false));
+        assert!(BodyDecoder::new(None).push(b"x\r\n").is_err());
+    }
+
+    #[test]
+    fn fixed_and_until_close() {
+        let mut decoder = BodyDecoder::new(Some(5));
+        let (data, consumed, _) = decode(&mut decoder, b"hello world", 4);
+        assert_eq!((data.as_slice(), consumed), (&b"hello"[..], 5));
+
+        let mut decoder = BodyDecoder::until_close();
+        let (n, event) = decoder.push(b"hello").unwrap();
+        assert_eq!((n, event), (5, Some(BodyEvent::Data(b"hello"))));
+        decoder.finish().unwrap();
+        assert_eq!(decoder.push(b"").unwrap(), (0, Some(BodyEvent::End)));
+        assert!(BodyDecoder::new(Some(1)).finish().is_err());
+    }
+}
@@ -0,0 +1,154 @@
+use crate::Error;
+use alloc::vec::Vec;
+use core::cmp::min;
+
+const BUFFER_HEAD: usize = 5;
+const BUFFER_TAIL: usize = 2;
+
+/// Sans-IO chunked body encoder. Data is buffered into chunks of up to 1293 bytes, which the
+/// caller takes from [`pending`](Self::pending) and writes to the transport.
+pub struct ChunkedEncoder {
+    buffer: [u8; 1300],
+    buffered: usize,
+    written: Option<usize>,
+    closing: bool,
+}
+
+impl ChunkedEncoder {
+    pub fn new() -> Self {
+        Self {
+            buffer: [0u8; 1300],
+            buffered: 0,
+            written: None,
+            closing: false,
+        }
+    }
+    /// Buffers data and returns the number of bytes accepted, which is zero while a chunk is
+    /// pending.
+    pub fn push(&mut self, data: &[u8]) -> Result<usize, Error> {
+        if self.closing && !data.is_empty() {
+            return Err(Error::InvalidBody("data after the last chunk"));
+        }
+        if self.written.is_some() {
+            return Ok(0);
+        }
+        let off = BUFFER_HEAD + self.buffered;
+        let n = min(data.len(), self.buffer.len() - off - BUFFER_TAIL);
+        self.buffer[off..off + n].copy_from_slice(&data[0..n]);
+        self.buffered += n;
+        if self.buffered + BUFFER_TAIL + BUFFER_HEAD == self.buffer.len() {
+            self.finalize_chunk();
+        }
+        Ok(n)
+    }
+    /// Turns buffered data into a pending chunk.
+    pub fn flush(&mut self) {
+        if self.buffered > 0 && self.written.is_none() {
+            self.finalize_chunk();
+        }
+    }
+    /// Turns buffered data into a pending chunk or, once nothing is buffered, queues the last
+    /// chunk. Repeat after writing out pending output until [`is_done`](Self::is_done).
+    pub fn finish(&mut self) {
+        if self.written.is_some() || self.closing {
+            return;
+        }
+        if self.buffered == 0 {
+            self.closing = true;
+        }
+        self.finalize_chunk();
+    }
+    /// Completes the body and returns the last chunk, carrying the trailer section `trailers`
+    /// in wire format including the final empty line. Buffered data must be written out first.
+    pub fn finish_with_trailers(&mut self, trailers: &[u8]) -> Result<Vec<u8>, Error> {
+        if self.closing || self.buffered > 0 {
+            return Err(Error::InvalidBody("data pending before the last chunk"));
+        }
+        self.closing = true;
+        Ok([b"0\r\n", trailers].concat())
+    }
+    /// Whether the last chunk was queued, after which no more data is accepted.
+    pub fn is_closing(&self) -> bool {
+        self.closing
+    }
+    /// Whether the last chunk was queued and written out.
+    pub fn is_done(&self) -> bool {
+        self.closing && self.written.is_none()
+    }
+    /// Encoded output that has yet to be written.
+    pub fn pending(&self) -> &[u8] {
+        match self.written {
+            Some(written) => &self.buffer[written..BUFFER_HEAD + self.buffered + BUFFER_TAIL],
+            None => &[],
+        }
+    }
+    /// Marks `n` bytes of [`pending`](Self::pending) output as written.
+    pub fn advance(&mut self, n: usize) {
+        if let Some(written) = self.written {
+            let end = BUFFER_HEAD + self.buffered + BUFFER_TAIL;
+            self.written = Some(min(written + n, end));
+            if self.written == Some(end) {
+                self.buffered = 0;
+                self.written = None;
+            }
+        }
+    }
+    fn finalize_chunk(&mut self) {
+        self.buffer[BUFFER_HEAD - 2..BUFFER_HEAD].copy_from_slice(b"\r\n");
+        let end = BUFFER_HEAD + self.buffered + BUFFER_TAIL;
+        self.buffer[end - 2..end].copy_from_slice(b"\r\n");
+        let start = size_digits(self.buffered, &mut self.buffer[0..BUFFER_HEAD - 2]);
+        self.written = Some(start);
+    }
+}
+
+impl Default for ChunkedEncoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// writes `len` as hex digits to the end of `buf`, returns the index of the first digit
+pub(crate) fn size_digits(mut len: usize, buf: &mut [u8]) -> usize {
+    let mut start = buf.len();
+    while len > 0 || start == buf.len() {
+        let digit = len & 15;
+        len /= 16;
+        start -= 1;
+        buf[start] = match digit {
+            0..=9 => b'0' + digit as u8,
+            10..=15 => b'A' - 10 + digit as u8,
+            _ => unreachable!(),
+        };
+    }
+    start
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ChunkedEncoder;
+    use alloc::vec::Vec;
+
+    #[test]
+    fn chunked() {
+        let mut encoder = ChunkedEncoder::new();
+        let mut output = Vec::new();
+        let mut input: &[u8] = &[b'x'; 1500];
+        while !input.is_empty() {
+            let n = encoder.push(input).unwrap();
+            input = &input[n..];
+            output.extend_from_slice(encoder.pending());
+            encoder.advance(encoder.pending().len());
+        }
+        while !encoder.is_done() {
+            encoder.finish();
+            output.extend_from_slice(encoder.pending());
+            encoder.advance(encoder.pending().len());
+        }
+        let tail = b"\r\nCF\r\n".len() + 207 + b"\r\n0\r\n\r\n".len();
+        assert!(output.starts_with(b"50D\r\nxxx"));
+        assert!(output[output.len() - tail..].starts_with(b"\r\nCF\r\nxxx"));
+        assert!(output.ends_with(b"xxx\r\n0\r\n\r\n"));
+        assert!(encoder.push(b"x").is_err());
+    }
+}
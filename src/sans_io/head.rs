@@ -0,0 +1,108 @@
+use crate::Error;
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use std::io::{self, Read};
+
+const END: &[u8; 4] = b"\r\n\r\n";
+
+/// Parses a complete head, given the maximum number of headers.
+pub type HeadDecodeFn<O> = dyn Fn(&[u8], usize) -> Result<O, Error> + Sync;
+
+/// Sans-IO head decoder. Input is pushed as byte slices of any size, consumption stops at the
+/// end of the head, so the remaining input belongs to the body.
+pub struct HeadDecoder<O: 'static> {
+    buffer: Vec<u8>,
+    max_head: usize,
+    max_headers: usize,
+    completion: usize,
+    decode_func: &'static HeadDecodeFn<O>,
+}
+
+impl<O> HeadDecoder<O> {
+    pub fn new(max_head: usize, max_headers: usize, decode_func: &'static HeadDecodeFn<O>) -> Self {
+        Self {
+            buffer: Vec::new(),
+            max_head,
+            max_headers,
+            completion: 0,
+            decode_func,
+        }
+    }
+    /// Maximum number of bytes that can be pushed next without reading past the end of the
+    /// head.
+    pub fn read_hint(&self) -> usize {
+        END.len() - self.completion
+    }
+    /// Bytes of the head pushed so far.
+    pub fn buffered(&self) -> &[u8] {
+        &self.buffer
+    }
+    /// Decodes input, returning the number of bytes consumed and the head once it is complete.
+    /// The decoder is reset after returning a head and can decode the next one.
+    pub fn push(&mut self, input: &[u8]) -> Result<(usize, Option<O>), Error> {
+        let mut consumed = 0;
+        while consumed < input.len() && self.completion < END.len() {
+            let byte = input[consumed];
+            consumed += 1;
+            self.completion = match byte == END[self.completion] {
+                true => self.completion + 1,
+                false => (byte == END[0]) as usize,
+            };
+        }
+        if self.buffer.len() + consumed > self.max_head {
+            return Err(Error::InvalidHead("head too long"));
+        }
+        self.buffer.extend_from_slice(&input[..consumed]);
+        if self.completion < END.len() {
+            return Ok((consumed, None));
+        }
+        let head = (self.decode_func)(&self.buffer, self.max_headers);
+        self.buffer.clear();
+        self.completion = 0;
+        Ok((consumed, Some(head?)))
+    }
+    /// Reads the next head from a blocking reader, without reading past its end.
+    #[cfg(feature = "std")]
+    pub fn read_from<R: Read>(&mut self, mut rd: R) -> io::Result<O> {
+        let mut chunk = [0u8; 4];
+        loop {
+            let chunk = &mut chunk[..self.read_hint()];
+            match rd.read(chunk) {
+                Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+                Ok(n) => {
+                    if let (_, Some(head)) = self.push(&chunk[..n])? {
+                        return Ok(head);
+                    }
+                }
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) => return Err(err),
+            }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use crate::RequestHead;
+
+    #[test]
+    fn push() {
+        let input = b"GET / HTTP/1.1\r\nhost: a\r\n\r\nbody";
+        for step in [1, 5, input.len()] {
+            let mut decoder = RequestHead::decoder();
+            let mut consumed = 0;
+            let head = loop {
+                let end = (consumed + step).min(input.len());
+                let (n, head) = decoder.push(&input[consumed..end]).unwrap();
+                consumed += n;
+                if let Some(head) = head {
+                    break head;
+                }
+            };
+            assert_eq!(head.headers()["host"], "a");
+            assert_eq!(&input[consumed..], b"body");
+        }
+        let mut decoder = RequestHead::decoder_with_limits(16, 8);
+        assert!(decoder.push(b"GET / HTTP/1.1\r\nhost: a").is_err());
+    }
+}
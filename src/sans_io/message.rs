@@ -0,0 +1,109 @@
+use crate::sans_io::{BodyDecoder, BodyEvent, HeadDecoder};
+use crate::RequestHead;
+use http::HeaderMap;
+use std::io;
+use std::io::ErrorKind::InvalidData;
+
+#[derive(Debug)]
+pub enum MessageEvent<'a, H> {
+    Head(H),
+    Data(&'a [u8]),
+    Trailers(HeaderMap),
+    /// End of the message, the decoder continues with the next one.
+    End,
+}
+
+/// Sans-IO decoder for the requests on a connection, combining [`HeadDecoder`] and
+/// [`BodyDecoder`].
+pub struct RequestDecoder {
+    head: HeadDecoder<RequestHead<'static>>,
+    body: Option<BodyDecoder>,
+}
+
+impl RequestDecoder {
+    pub fn new() -> Self {
+        Self::with_limits(8192, 128)
+    }
+    pub fn with_limits(max_head: usize, max_headers: usize) -> Self {
+        Self {
+            head: RequestHead::decoder_with_limits(max_head, max_headers),
+            body: None,
+        }
+    }
+    /// Maximum number of bytes that can be pushed next without reading past the current message.
+    pub fn read_hint(&self) -> usize {
+        match &self.body {
+            Some(body) => body.read_hint().max(1),
+            None => self.head.read_hint(),
+        }
+    }
+    /// Decodes input until an event occurs, returning the number of bytes consumed. Returns no
+    /// event if all input was consumed without one.
+    pub fn push<'a>(
+        &mut self,
+        input: &'a [u8],
+    ) -> io::Result<(usize, Option<MessageEvent<'a, RequestHead<'static>>>)> {
+        let body = match &mut self.body {
+            Some(body) => body,
+            None => {
+                return match self.head.push(input)? {
+                    (n, Some(head)) => {
+                        let body = BodyDecoder::from_headers(head.headers())
+                            .map_err(|err| io::Error::new(InvalidData, err.to_string()))?;
+                        self.body = Some(body);
+                        Ok((n, Some(MessageEvent::Head(head))))
+                    }
+                    (n, None) => Ok((n, None)),
+                }
+            }
+        };
+        let (n, event) = body.push(input)?;
+        let event = match event {
+            Some(BodyEvent::Data(data)) => Some(MessageEvent::Data(data)),
+            Some(BodyEvent::Trailers(trailers)) => {
+                Some(MessageEvent::Trailers(trailers.to_headers()?))
+            }
+            Some(BodyEvent::End) => {
+                self.body = None;
+                Some(MessageEvent::End)
+            }
+            None => None,
+        };
+        Ok((n, event))
+    }
+}
+
+impl Default for RequestDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{MessageEvent, RequestDecoder};
+
+    #[test]
+    fn pipelined() {
+        let mut input: &[u8] = b"POST /a HTTP/1.1\r\ntransfer-encoding: chunked\r\n\r\n3\r\nabc\r\n0\r\nx-sum: 1\r\n\r\n\
+            GET /b HTTP/1.1\r\n\r\n";
+        let mut events = Vec::new();
+        let mut decoder = RequestDecoder::new();
+        loop {
+            let (n, event) = decoder.push(input).unwrap();
+            input = &input[n..];
+            match event {
+                Some(MessageEvent::Head(head)) => events.push(head.uri().to_string()),
+                Some(MessageEvent::Data(data)) => events.push(String::from_utf8_lossy(data).into()),
+                Some(MessageEvent::Trailers(trailers)) => events.push(format!("{:?}", trailers)),
+                Some(MessageEvent::End) => events.push("end".to_string()),
+                None => break,
+            }
+        }
+        assert_eq!(
+            events,
+            ["/a", "abc", "{\"x-sum\": \"1\"}", "end", "/b", "end"]
+        );
+        assert!(input.is_empty());
+    }
+}
@@ -0,0 +1,13 @@
+mod body;
+mod encode;
+mod head;
+#[cfg(feature = "std")]
+mod message;
+mod raw_head;
+
+pub use body::*;
+pub use encode::*;
+pub use head::*;
+#[cfg(feature = "std")]
+pub use message::*;
+pub use raw_head::*;
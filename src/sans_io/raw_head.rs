@@ -0,0 +1,272 @@
+use crate::Error;
+use crate::HeadDecoder;
+use alloc::borrow::Cow;
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// Header field of a raw head. Names are not normalized, values are bytes as received.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RawHeader<'a> {
+    pub name: Cow<'a, str>,
+    pub value: Cow<'a, [u8]>,
+}
+
+/// HTTP/1 request head without `http` types, borrowing from the parsed input. `version` is the
+/// minor version, 0 for HTTP/1.0 and 1 for HTTP/1.1.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RawRequestHead<'a> {
+    pub method: Cow<'a, str>,
+    pub target: Cow<'a, str>,
+    pub version: u8,
+    pub headers: Vec<RawHeader<'a>>,
+}
+
+/// HTTP/1 response head without `http` types, borrowing from the parsed input. `version` is the
+/// minor version, 0 for HTTP/1.0 and 1 for HTTP/1.1.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RawResponseHead<'a> {
+    pub version: u8,
+    pub status: u16,
+    pub reason: Cow<'a, str>,
+    pub headers: Vec<RawHeader<'a>>,
+}
+
+impl<'a> RawHeader<'a> {
+    pub fn into_owned(self) -> RawHeader<'static> {
+        RawHeader {
+            name: Cow::Owned(self.name.into_owned()),
+            value: Cow::Owned(self.value.into_owned()),
+        }
+    }
+}
+
+impl<'a> RawRequestHead<'a> {
+    /// Parses a complete head, which must end with an empty line.
+    pub fn parse(buffer: &'a [u8], max_headers: usize) -> Result<Self, Error> {
+        let mut headers = vec![httparse::EMPTY_HEADER; max_headers];
+        let mut parsed = httparse::Request::new(&mut headers);
+        if parsed.parse(buffer).map_err(Error::Parse)?.is_partial() {
+            return Err(Error::InvalidHead("malformed HTTP head"));
+        }
+        Ok(Self {
+            method: Cow::Borrowed(parsed.method.unwrap_or("")),
+            target: Cow::Borrowed(parsed.path.unwrap_or("")),
+            version: parsed.version.unwrap_or(1),
+            headers: raw_headers(parsed.headers),
+        })
+    }
+    /// Appends the encoded head to `buffer`.
+    pub fn encode(&self, buffer: &mut Vec<u8>) -> Result<(), Error> {
+        check_token(&self.method, "invalid method")?;
+        check_line(self.target.as_bytes(), "invalid request target")?;
+        if self.target.is_empty() || self.target.contains(' ') {
+            return Err(Error::InvalidHead("invalid request target"));
+        }
+        push_all(
+            buffer,
+            &[self.method.as_bytes(), b" ", self.target.as_bytes(), b" "],
+        );
+        encode_version(buffer, self.version)?;
+        buffer.extend_from_slice(b"\r\n");
+        encode_headers(buffer, &self.headers)
+    }
+    pub fn into_owned(self) -> RawRequestHead<'static> {
+        RawRequestHead {
+            method: Cow::Owned(self.method.into_owned()),
+            target: Cow::Owned(self.target.into_owned()),
+            version: self.version,
+            headers: self
+                .headers
+                .into_iter()
+                .map(RawHeader::into_owned)
+                .collect(),
+        }
+    }
+    pub fn decoder() -> HeadDecoder<RawRequestHead<'static>> {
+        Self::decoder_with_limits(8192, 128)
+    }
+    pub fn decoder_with_limits(
+        max_head: usize,
+        max_headers: usize,
+    ) -> HeadDecoder<RawRequestHead<'static>> {
+        HeadDecoder::new(max_head, max_headers, &parse_owned_request)
+    }
+}
+
+impl<'a> RawResponseHead<'a> {
+    /// Parses a complete head, which must end with an empty line.
+    pub fn parse(buffer: &'a [u8], max_headers: usize) -> Result<Self, Error> {
+        let mut headers = vec![httparse::EMPTY_HEADER; max_headers];
+        let mut parsed = httparse::Response::new(&mut headers);
+        if parsed.parse(buffer).map_err(Error::Parse)?.is_partial() {
+            return Err(Error::InvalidHead("malformed HTTP head"));
+        }
+        Ok(Self {
+            version: parsed.version.unwrap_or(1),
+            status: parsed.code.unwrap_or(0),
+            reason: Cow::Borrowed(parsed.reason.unwrap_or("")),
+            headers: raw_headers(parsed.headers),
+        })
+    }
+    /// Appends the encoded head to `buffer`.
+    pub fn encode(&self, buffer: &mut Vec<u8>) -> Result<(), Error> {
+        if !(100..=999).contains(&self.status) {
+            return Err(Error::InvalidHead("invalid status code"));
+        }
+        check_line(self.reason.as_bytes(), "invalid reason phrase")?;
+        encode_version(buffer, self.version)?;
+        let status = [
+            b' ',
+            b'0' + (self.status / 100) as u8,
+            b'0' + (self.status / 10 % 10) as u8,
+            b'0' + (self.status % 10) as u8,
+            b' ',
+        ];
+        push_all(buffer, &[&status, self.reason.as_bytes(), b"\r\n"]);
+        encode_headers(buffer, &self.headers)
+    }
+    pub fn into_owned(self) -> RawResponseHead<'static> {
+        RawResponseHead {
+            version: self.version,
+            status: self.status,
+            reason: Cow::Owned(self.reason.into_owned()),
+            headers: self
+                .headers
+                .into_iter()
+                .map(RawHeader::into_owned)
+                .collect(),
+        }
+    }
+    pub fn decoder() -> HeadDecoder<RawResponseHead<'static>> {
+        Self::decoder_with_limits(8192, 128)
+    }
+    pub fn decoder_with_limits(
+        max_head: usize,
+        max_headers: usize,
+    ) -> HeadDecoder<RawResponseHead<'static>> {
+        HeadDecoder::new(max_head, max_headers, &parse_owned_response)
+    }
+}
+
+/// Appends the header fields and the empty line ending the head to `buffer`.
+pub fn encode_headers(buffer: &mut Vec<u8>, headers: &[RawHeader]) -> Result<(), Error> {
+    for header in headers {
+        check_token(&header.name, "invalid header name")?;
+        check_line(&header.value, "invalid character in header value")?;
+        push_all(
+            buffer,
+            &[header.name.as_bytes(), b": ", &header.value, b"\r\n"],
+        );
+    }
+    buffer.extend_from_slice(b"\r\n");
+    Ok(())
+}
+
+fn parse_owned_request(
+    buffer: &[u8],
+    max_headers: usize,
+) -> Result<RawRequestHead<'static>, Error> {
+    RawRequestHead::parse(buffer, max_headers).map(RawRequestHead::into_owned)
+}
+
+fn parse_owned_response(
+    buffer: &[u8],
+    max_headers: usize,
+) -> Result<RawResponseHead<'static>, Error> {
+    RawResponseHead::parse(buffer, max_headers).map(RawResponseHead::into_owned)
+}
+
+fn raw_headers<'a>(parsed: &[httparse::Header<'a>]) -> Vec<RawHeader<'a>> {
+    parsed
+        .iter()
+        .map(|header| RawHeader {
+            name: Cow::Borrowed(header.name),
+            value: Cow::Borrowed(header.value),
+        })
+        .collect()
+}
+
+fn encode_version(buffer: &mut Vec<u8>, version: u8) -> Result<(), Error> {
+    if version > 1 {
+        return Err(Error::InvalidHead("unsupported HTTP version"));
+    }
+    push_all(buffer, &[b"HTTP/1.", &[b'0' + version]]);
+    Ok(())
+}
+
+fn push_all(buffer: &mut Vec<u8>, parts: &[&[u8]]) {
+    for part in parts {
+        buffer.extend_from_slice(part);
+    }
+}
+
+/// Rejects empty tokens and characters outside of RFC 9110 `tchar`.
+fn check_token(token: &str, msg: &'static str) -> Result<(), Error> {
+    let tchar = |b: &u8| b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(b);
+    match !token.is_empty() && token.as_bytes().iter().all(tchar) {
+        true => Ok(()),
+        false => Err(Error::InvalidHead(msg)),
+    }
+}
+
+/// Rejects control characters other than horizontal tab, which could end the line early.
+fn check_line(line: &[u8], msg: &'static str) -> Result<(), Error> {
+    match line.iter().all(|&b| b == b'\t' || (b >= b' ' && b != 0x7f)) {
+        true => Ok(()),
+        false => Err(Error::InvalidHead(msg)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn request() {
+        let input = b"POST /submit?x=1 HTTP/1.1\r\nHost: a\r\ncontent-length: 4\r\n\r\nbody";
+        let mut decoder = RawRequestHead::decoder();
+        let (consumed, head) = decoder.push(input).unwrap();
+        let head = head.unwrap();
+        assert_eq!(&input[consumed..], b"body");
+        assert_eq!(head.method, "POST");
+        assert_eq!(head.target, "/submit?x=1");
+        assert_eq!(head.version, 1);
+        assert_eq!(head.headers.len(), 2);
+        assert_eq!(head.headers[0].name, "Host");
+        assert_eq!(&*head.headers[1].value, b"4");
+        let mut encoded = Vec::new();
+        head.encode(&mut encoded).unwrap();
+        assert_eq!(encoded, &input[..consumed]);
+        assert!(RawRequestHead::parse(b"GET / HTTP/1.1\r\nhost: a\r\n", 8).is_err());
+        assert!(RawRequestHead::parse(input, 1).is_err());
+    }
+
+    #[test]
+    fn response() {
+        let input = b"HTTP/1.0 404 Not Found\r\ncontent-length: 0\r\n\r\n";
+        let head = RawResponseHead::parse(input, 8).unwrap();
+        assert_eq!((head.version, head.status), (0, 404));
+        assert_eq!(head.reason, "Not Found");
+        let mut encoded = Vec::new();
+        head.encode(&mut encoded).unwrap();
+        assert_eq!(encoded, input);
+    }
+
+    #[test]
+    fn encode_invalid() {
+        let mut head = RawResponseHead::parse(b"HTTP/1.1 200 OK\r\n\r\n", 8).unwrap();
+        head.headers.push(RawHeader {
+            name: Cow::Borrowed("x"),
+            value: Cow::Borrowed(b"a\r\nb: c"),
+        });
+        assert_eq!(
+            head.encode(&mut Vec::new()),
+            Err(Error::InvalidHead("invalid character in header value"))
+        );
+        head.headers[0].name = Cow::Borrowed("a b");
+        assert!(head.encode(&mut Vec::new()).is_err());
+        head.headers.clear();
+        head.version = 2;
+        assert!(head.encode(&mut Vec::new()).is_err());
+    }
+}
@@ -0,0 +1,163 @@
+use crate::tokio::{poll_read_buf, Compat};
+use crate::{BodyDecodeState, BodyDecodeWithContinueState, Drained, RequestHead, ResponseHead};
+use futures::future;
+use http::{HeaderMap, Method, Version};
+use std::borrow::BorrowMut;
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+/// Like [`crate::BodyDecode`], for tokio transports.
+pub struct BodyDecode<T: BorrowMut<BodyDecodeState> + Unpin, IO: AsyncRead + Unpin> {
+    transport: IO,
+    state: T,
+}
+
+impl<IO: AsyncRead + Unpin> BodyDecode<BodyDecodeState, IO> {
+    pub fn new(transport: IO, length: Option<u64>) -> Self {
+        Self::from_state(BodyDecodeState::new(length), transport)
+    }
+    pub fn from_headers(headers: &HeaderMap, transport: IO) -> anyhow::Result<Self> {
+        Ok(Self::from_state(
+            BodyDecodeState::from_headers(headers)?,
+            transport,
+        ))
+    }
+    pub fn from_response_head(
+        head: &ResponseHead,
+        request_method: &Method,
+        transport: IO,
+    ) -> anyhow::Result<Self> {
+        Ok(Self::from_state(
+            BodyDecodeState::from_response_head(head, request_method)?,
+            transport,
+        ))
+    }
+}
+
+impl<T: BorrowMut<BodyDecodeState> + Unpin, IO: AsyncRead + Unpin> BodyDecode<T, IO> {
+    pub fn from_state(state: T, transport: IO) -> Self {
+        Self { transport, state }
+    }
+    pub fn into_inner(self) -> (T, IO) {
+        (self.state, self.transport)
+    }
+    /// Returns the transport if the body has been consumed completely.
+    pub fn into_transport(self) -> Result<IO, Self> {
+        match self.state.borrow().is_done() {
+            true => Ok(self.transport),
+            false => Err(self),
+        }
+    }
+    /// Discards the rest of the body, reading at most `limit` body bytes.
+    pub fn drain(&mut self, mut limit: u64) -> impl Future<Output = io::Result<Drained>> + '_ {
+        future::poll_fn(move |cx| {
+            self.state
+                .borrow_mut()
+                .poll_drain(&mut Compat(&mut self.transport), cx, &mut limit)
+        })
+    }
+}
+
+impl<T: BorrowMut<BodyDecodeState> + Unpin, IO: AsyncRead + Unpin> AsyncRead for BodyDecode<T, IO> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        poll_read_buf(buf, |buf| {
+            this.state
+                .borrow_mut()
+                .poll_read(&mut Compat(&mut this.transport), cx, buf)
+        })
+    }
+}
+
+/// Like [`crate::BodyDecodeWithContinue`], for tokio transports.
+pub struct BodyDecodeWithContinue<
+    T: BorrowMut<BodyDecodeWithContinueState> + Unpin,
+    IO: AsyncRead + AsyncWrite + Unpin,
+> {
+    io: IO,
+    state: T,
+}
+
+impl<IO: AsyncRead + AsyncWrite + Unpin> BodyDecodeWithContinue<BodyDecodeWithContinueState, IO> {
+    pub fn from_head(head: &RequestHead, io: IO) -> anyhow::Result<Self> {
+        Ok(Self::from_state(
+            BodyDecodeWithContinueState::from_head(head)?,
+            io,
+        ))
+    }
+    pub fn from_headers(headers: &HeaderMap, version: Version, io: IO) -> anyhow::Result<Self> {
+        Ok(Self::from_state(
+            BodyDecodeWithContinueState::from_headers(headers, version)?,
+            io,
+        ))
+    }
+    pub fn new(io: IO, version: Version, length: Option<u64>, send_continue: bool) -> Self {
+        Self::from_state(
+            BodyDecodeWithContinueState::new(version, length, send_continue),
+            io,
+        )
+    }
+}
+
+impl<T: BorrowMut<BodyDecodeWithContinueState> + Unpin, IO: AsyncRead + AsyncWrite + Unpin>
+    BodyDecodeWithContinue<T, IO>
+{
+    pub fn from_state(state: T, io: IO) -> Self {
+        Self { io, state }
+    }
+    pub fn into_inner(self) -> (T, IO) {
+        (self.state, self.io)
+    }
+    pub fn state(&self) -> &BodyDecodeWithContinueState {
+        self.state.borrow()
+    }
+    /// Discards the rest of the body, reading at most `limit` body bytes.
+    pub fn drain(&mut self, mut limit: u64) -> impl Future<Output = io::Result<Drained>> + '_ {
+        future::poll_fn(move |cx| {
+            self.state
+                .borrow_mut()
+                .poll_drain(cx, &mut Compat(&mut self.io), &mut limit)
+        })
+    }
+    /// Sends `100 Continue` now if it is pending.
+    pub fn accept(&mut self) -> impl Future<Output = io::Result<()>> + '_ {
+        future::poll_fn(move |cx| {
+            self.state
+                .borrow_mut()
+                .poll_accept(cx, &mut Compat(&mut self.io))
+        })
+    }
+    /// Like [`crate::BodyDecodeWithContinue::reject`].
+    pub fn reject(&mut self, head: ResponseHead<'_>) -> impl Future<Output = io::Result<()>> + '_ {
+        let mut response = self.state.borrow().reject_response(head);
+        future::poll_fn(move |cx| {
+            self.state
+                .borrow_mut()
+                .poll_reject_with(cx, &mut Compat(&mut self.io), &mut response)
+        })
+    }
+}
+
+impl<T: BorrowMut<BodyDecodeWithContinueState> + Unpin, IO: AsyncRead + AsyncWrite + Unpin>
+    AsyncRead for BodyDecodeWithContinue<T, IO>
+{
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        poll_read_buf(buf, |buf| {
+            this.state
+                .borrow_mut()
+                .poll_read(cx, buf, &mut Compat(&mut this.io))
+        })
+    }
+}
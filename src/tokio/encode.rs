@@ -0,0 +1,92 @@
+use crate::tokio::Compat;
+use crate::{BodyEncodeState, Framing, RequestHead, ResponseHead};
+use futures::future;
+use http::{HeaderMap, Method, Version};
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::io::AsyncWrite;
+
+/// Like [`crate::BodyEncode`], for tokio transports. Shutting down completes the body.
+pub struct BodyEncode<IO: AsyncWrite + Unpin> {
+    transport: IO,
+    state: BodyEncodeState,
+}
+
+impl<IO: AsyncWrite + Unpin> BodyEncode<IO> {
+    pub fn new(transport: IO, length: Option<u64>) -> Self {
+        Self::from_state(BodyEncodeState::new(length), transport)
+    }
+    pub fn from_headers(headers: &HeaderMap, transport: IO) -> anyhow::Result<Self> {
+        Ok(Self::from_state(
+            BodyEncodeState::from_headers(headers)?,
+            transport,
+        ))
+    }
+    pub fn from_response_head(
+        head: &mut ResponseHead,
+        request_method: &Method,
+        peer: Version,
+        transport: IO,
+    ) -> anyhow::Result<Self> {
+        Ok(Self::from_state(
+            BodyEncodeState::from_response_head(head, request_method, peer)?,
+            transport,
+        ))
+    }
+    pub fn from_framing(framing: Framing, transport: IO) -> Self {
+        Self::from_state(BodyEncodeState::from_framing(framing), transport)
+    }
+    pub fn for_request(
+        head: &mut RequestHead,
+        length: Option<u64>,
+        peer: Version,
+        transport: IO,
+    ) -> anyhow::Result<Self> {
+        Ok(Self::from_state(
+            BodyEncodeState::for_request(head, length, peer)?,
+            transport,
+        ))
+    }
+    pub fn for_response(
+        head: &mut ResponseHead,
+        length: Option<u64>,
+        peer: Version,
+        transport: IO,
+    ) -> Self {
+        Self::from_state(BodyEncodeState::for_response(head, length, peer), transport)
+    }
+    pub fn from_state(state: BodyEncodeState, transport: IO) -> Self {
+        Self { transport, state }
+    }
+    pub fn checkpoint(self) -> (IO, BodyEncodeState) {
+        (self.transport, self.state)
+    }
+    /// Completes the body without shutting down the transport, see
+    /// [`BodyEncodeState::poll_finish`].
+    pub fn finish(&mut self) -> impl Future<Output = io::Result<()>> + '_ {
+        future::poll_fn(move |cx| self.state.poll_finish(Compat(&mut self.transport), cx))
+    }
+}
+
+impl<IO: AsyncWrite + Unpin> AsyncWrite for BodyEncode<IO> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        this.state.poll_write(Compat(&mut this.transport), cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        this.state.poll_flush(Compat(&mut this.transport), cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        this.state.poll_close(Compat(&mut this.transport), cx)
+    }
+}
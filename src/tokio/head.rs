@@ -0,0 +1,76 @@
+use crate::internal::buffer_decode::BufferDecodeState;
+use crate::internal::buffer_write::BufferWriteState;
+use crate::internal::io_future::{IoFutureState, IoFutureWithOutputState};
+use crate::tokio::Compat;
+use crate::{RequestHead, ResponseHead};
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, AsyncWrite};
+
+/// Future decoding a head from a tokio transport, yielding the transport and the head.
+pub struct HeadDecode<IO: AsyncRead + Unpin, O: 'static>(Option<(BufferDecodeState<O>, IO)>);
+
+impl<IO: AsyncRead + Unpin, O> HeadDecode<IO, O> {
+    pub fn new(state: BufferDecodeState<O>, io: IO) -> Self {
+        Self(Some((state, io)))
+    }
+    pub fn checkpoint(self) -> (BufferDecodeState<O>, IO) {
+        self.0.unwrap()
+    }
+}
+
+impl<IO: AsyncRead + Unpin, O> Future for HeadDecode<IO, O> {
+    type Output = io::Result<(IO, O)>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let (state, io) = self.0.as_mut().unwrap();
+        let p = state.poll(cx, &mut Compat(io));
+        p.map(|r| r.map(|o| (self.0.take().unwrap().1, o)))
+    }
+}
+
+/// Future writing a head to a tokio transport, yielding the transport.
+pub struct HeadEncode<IO: AsyncWrite + Unpin>(Option<(BufferWriteState, IO)>);
+
+impl<IO: AsyncWrite + Unpin> HeadEncode<IO> {
+    pub fn new(state: BufferWriteState, io: IO) -> Self {
+        Self(Some((state, io)))
+    }
+    pub fn checkpoint(self) -> (BufferWriteState, IO) {
+        self.0.unwrap()
+    }
+}
+
+impl<IO: AsyncWrite + Unpin> Future for HeadEncode<IO> {
+    type Output = io::Result<IO>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let (state, io) = self.0.as_mut().unwrap();
+        let p = state.poll(cx, &mut Compat(io));
+        p.map(|r| r.map(|()| self.0.take().unwrap().1))
+    }
+}
+
+impl RequestHead<'_> {
+    /// Like [`decode`](Self::decode), for tokio transports.
+    pub fn decode_tokio<IO: AsyncRead + Unpin>(io: IO) -> HeadDecode<IO, RequestHead<'static>> {
+        HeadDecode::new(RequestHead::decode_state(), io)
+    }
+    /// Like [`encode`](Self::encode), for tokio transports.
+    pub fn encode_tokio<IO: AsyncWrite + Unpin>(&self, io: IO) -> HeadEncode<IO> {
+        HeadEncode::new(self.encode_state(), io)
+    }
+}
+
+impl ResponseHead<'_> {
+    /// Like [`decode`](Self::decode), for tokio transports.
+    pub fn decode_tokio<IO: AsyncRead + Unpin>(io: IO) -> HeadDecode<IO, ResponseHead<'static>> {
+        HeadDecode::new(ResponseHead::decode_state(), io)
+    }
+    /// Like [`encode`](Self::encode), for tokio transports.
+    pub fn encode_tokio<IO: AsyncWrite + Unpin>(&self, io: IO) -> HeadEncode<IO> {
+        HeadEncode::new(self.encode_state(), io)
+    }
+}
@@ -0,0 +1,187 @@
+mod decode;
+mod encode;
+mod head;
+
+pub use decode::*;
+pub use encode::*;
+pub use head::*;
+
+use futures::ready;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::io::ReadBuf;
+
+/// Exposes a tokio transport through the `futures` IO traits the state types are polled with.
+pub(crate) struct Compat<IO>(pub(crate) IO);
+
+impl<IO: tokio::io::AsyncRead + Unpin> futures::AsyncRead for Compat<IO> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let mut buf = ReadBuf::new(buf);
+        ready!(Pin::new(&mut self.get_mut().0).poll_read(cx, &mut buf))?;
+        Poll::Ready(Ok(buf.filled().len()))
+    }
+}
+
+impl<IO: tokio::io::AsyncWrite + Unpin> futures::AsyncWrite for Compat<IO> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.get_mut().0).poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().0).poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().0).poll_shutdown(cx)
+    }
+}
+
+/// Reads into the unfilled part of `buf` through a poll function taking a byte slice.
+pub(crate) fn poll_read_buf(
+    buf: &mut ReadBuf<'_>,
+    poll_read: impl FnOnce(&mut [u8]) -> Poll<io::Result<usize>>,
+) -> Poll<io::Result<()>> {
+    let n = ready!(poll_read(buf.initialize_unfilled()))?;
+    buf.advance(n);
+    Poll::Ready(Ok(()))
+}
+
+/// The head, body and continue tests run against both the `futures` and the tokio wrappers.
+#[cfg(test)]
+macro_rules! runtime_tests {
+    ($runtime:ident, $run:path, $decode:ident, $encode:ident, $($body:ident)::+, $($ext:tt)*) => {
+        mod $runtime {
+            use $($body)::+::{BodyDecode, BodyDecodeWithContinue, BodyEncode};
+            use crate::internal::mock_io::MockIo;
+            use crate::{Drained, RequestHead, ResponseHead};
+            use http::{HeaderMap, StatusCode, Version};
+            use std::borrow::Cow;
+            $($ext)*
+
+            const CHUNKED: &[u8] = b"6\r\nhello \r\n6\r\nworld!\r\n0\r\n\r\n";
+            const EXPECT_CONTINUE: &[u8] =
+                b"PUT / HTTP/1.1\r\ncontent-length: 5\r\nexpect: 100-continue\r\n\r\n";
+
+            #[test]
+            fn request_head_and_body() {
+                $run(async {
+                    const HEAD: &[u8] = b"PUT / HTTP/1.1\r\ntransfer-encoding: chunked\r\n\r\n";
+                    let input = [HEAD, CHUNKED].concat();
+                    let (transport, head) = RequestHead::$decode(&input[..]).await.unwrap();
+                    let mut body = BodyDecode::from_headers(head.headers(), transport).unwrap();
+                    let mut decoded = String::new();
+                    body.read_to_string(&mut decoded).await.unwrap();
+                    assert_eq!(decoded, "hello world!");
+                    assert!(body.into_inner().1.is_empty());
+
+                    let mut output = head.$encode(Vec::new()).await.unwrap();
+                    let mut encode = BodyEncode::from_headers(head.headers(), &mut output).unwrap();
+                    encode.write_all(b"hello ").await.unwrap();
+                    encode.flush().await.unwrap();
+                    encode.write_all(b"world!").await.unwrap();
+                    encode.finish().await.unwrap();
+                    assert_eq!(output, input);
+                })
+            }
+
+            #[test]
+            fn response_head() {
+                $run(async {
+                    const HEAD: &[u8] = b"HTTP/1.1 201 Created\r\nconnection: close\r\n\r\nnext";
+                    let (transport, head) = ResponseHead::$decode(HEAD).await.unwrap();
+                    assert_eq!(head.status(), StatusCode::CREATED);
+                    assert_eq!(head.headers()["connection"], "close");
+                    assert_eq!(transport, b"next");
+                    let output = head.$encode(Vec::new()).await.unwrap();
+                    assert_eq!(output, &HEAD[..HEAD.len() - 4]);
+                })
+            }
+
+            #[test]
+            fn drain() {
+                $run(async {
+                    let input = [CHUNKED, b"next"].concat();
+                    let mut body = BodyDecode::new(&input[..], None);
+                    assert_eq!(body.drain(12).await.unwrap(), Drained::Complete);
+                    assert_eq!(body.into_transport().ok().unwrap(), b"next");
+
+                    let mut body = BodyDecode::new(&b"hello world!"[..], Some(12));
+                    assert_eq!(body.drain(11).await.unwrap(), Drained::MustClose);
+                    assert_eq!(body.drain(u64::MAX).await.unwrap(), Drained::Complete);
+                })
+            }
+
+            #[test]
+            fn continue_on_read() {
+                $run(async {
+                    let (_, head) = RequestHead::$decode(EXPECT_CONTINUE).await.unwrap();
+                    let mut io = MockIo::new(b"hello");
+                    let mut body = BodyDecodeWithContinue::from_head(&head, &mut io).unwrap();
+                    assert!(body.state().is_continue_pending());
+                    let mut decoded = String::new();
+                    body.read_to_string(&mut decoded).await.unwrap();
+                    assert_eq!(decoded, "hello");
+                    assert_eq!(io.output, b"HTTP/1.1 100 Continue\r\n\r\n");
+                })
+            }
+
+            #[test]
+            fn reject() {
+                $run(async {
+                    let (_, head) = RequestHead::$decode(EXPECT_CONTINUE).await.unwrap();
+                    let mut io = MockIo::new(b"hello");
+                    let mut body = BodyDecodeWithContinue::from_head(&head, &mut io).unwrap();
+                    let response = ResponseHead::new(
+                        StatusCode::PAYLOAD_TOO_LARGE,
+                        Version::HTTP_11,
+                        Cow::Owned(HeaderMap::new()),
+                    );
+                    body.reject(response).await.unwrap();
+                    assert!(body.read(&mut [0u8; 5]).await.is_err());
+                    assert_eq!(body.drain(1024).await.unwrap(), Drained::MustClose);
+                    assert_eq!(
+                        io.output,
+                        b"HTTP/1.1 413 Payload Too Large\r\nconnection: close\r\n\r\n"
+                    );
+                })
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+fn block_on_tokio<F: std::future::Future>(future: F) -> F::Output {
+    tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap()
+        .block_on(future)
+}
+
+#[cfg(test)]
+runtime_tests!(
+    futures_tests,
+    futures::executor::block_on,
+    decode,
+    encode,
+    crate,
+    use futures::{AsyncReadExt, AsyncWriteExt};
+);
+
+#[cfg(test)]
+runtime_tests!(
+    tokio_tests,
+    super::block_on_tokio,
+    decode_tokio,
+    encode_tokio,
+    crate::tokio,
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+);
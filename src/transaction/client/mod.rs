@@ -0,0 +1,11 @@
+mod pipeline;
+mod proxy;
+mod send;
+mod send_with_continue;
+mod upgrade;
+
+pub use pipeline::*;
+pub use proxy::*;
+pub use send::*;
+pub use send_with_continue::*;
+pub use upgrade::*;
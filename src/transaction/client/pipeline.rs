@@ -0,0 +1,230 @@
+use crate::transaction::client::{body_state, send_body};
+use crate::{BodyDecode, BodyDecodeState, Drained, RequestHead, ResponseHead};
+use anyhow::bail;
+use futures::prelude::*;
+use std::collections::VecDeque;
+use std::io;
+
+/// Client side of a connection that sends requests without waiting for previous responses.
+///
+/// Responses are returned in the order the requests were sent. Once the server ends the
+/// connection, each outstanding request fails with an [`io::ErrorKind::ConnectionAborted`]
+/// error, after which it may be retried on a new connection. The same happens if the unread
+/// rest of a response body exceeds the drain limit.
+pub struct Pipeline<IO: AsyncRead + AsyncWrite + Unpin> {
+    io: IO,
+    outstanding: VecDeque<RequestHead<'static>>,
+    body: BodyDecodeState,
+    drain_limit: u64,
+    persistent: bool,
+    closing: bool,
+    /// Set while a response is decoded, and left set if that didn't complete.
+    poisoned: bool,
+}
+
+impl<IO: AsyncRead + AsyncWrite + Unpin> Pipeline<IO> {
+    pub fn new(io: IO) -> Self {
+        Self::with_drain_limit(io, 64 * 1024)
+    }
+    /// Unread response bodies are discarded up to `drain_limit` bytes before the next response
+    /// is decoded. Longer ones end the connection.
+    pub fn with_drain_limit(io: IO, drain_limit: u64) -> Self {
+        Self {
+            io,
+            outstanding: VecDeque::new(),
+            body: BodyDecodeState::new(Some(0)),
+            drain_limit,
+            persistent: true,
+            closing: false,
+            poisoned: false,
+        }
+    }
+    pub fn into_inner(self) -> IO {
+        self.io
+    }
+    /// Number of requests still waiting for a response.
+    pub fn outstanding(&self) -> usize {
+        self.outstanding.len()
+    }
+    /// Sends a request, framing the body like [`send`](crate::client::send).
+    ///
+    /// Fails if a previous request or response ended persistence of the connection.
+    pub async fn send<B: AsyncRead + Unpin>(
+        &mut self,
+        mut head: RequestHead<'_>,
+        body: B,
+    ) -> anyhow::Result<()> {
+        if self.closing || self.poisoned {
+            bail!(io::Error::new(
+                io::ErrorKind::ConnectionAborted,
+                "connection does not persist"
+            ))
+        }
+        let state = body_state(&mut head)?;
+        head.encode(&mut self.io).await?;
+        send_body(&mut self.io, state, body).await?;
+        self.closing = !head.is_persistent();
+        self.outstanding.push_back(head.to_owned());
+        Ok(())
+    }
+    /// Decodes the response to the oldest outstanding request, after discarding the unread
+    /// body of the previous response. Returns `None` if there is no outstanding request.
+    pub async fn next_response(
+        &mut self,
+    ) -> anyhow::Result<
+        Option<(
+            ResponseHead<'static>,
+            BodyDecode<&mut BodyDecodeState, &mut IO>,
+        )>,
+    > {
+        let request = match self.outstanding.pop_front() {
+            Some(request) => request,
+            None => return Ok(None),
+        };
+        if self.poisoned {
+            bail!(aborted())
+        }
+        self.poisoned = true;
+        let mut limit = self.drain_limit;
+        let drained =
+            future::poll_fn(|cx| self.body.poll_drain(&mut self.io, cx, &mut limit)).await?;
+        if drained == Drained::MustClose || !self.persistent {
+            bail!(aborted())
+        }
+        let head = match ResponseHead::decode_final(&mut self.io, |_| {}).await {
+            Ok((_, head)) => head,
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => bail!(aborted()),
+            Err(err) => return Err(err.into()),
+        };
+        self.body = BodyDecodeState::from_response_head(&head, &request.method())?;
+        self.persistent = head.is_persistent(&request) && !self.body.is_close_delimited();
+        self.closing |= !self.persistent;
+        self.poisoned = false;
+        Ok(Some((head, self.body.as_async_read(&mut self.io))))
+    }
+}
+
+fn aborted() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::ConnectionAborted,
+        "connection closed before the response was received",
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::client::Pipeline;
+    use crate::internal::mock_io::MockIo;
+    use crate::RequestHead;
+    use futures::executor::block_on;
+    use futures::prelude::*;
+    use http::{Method, Request};
+    use std::io;
+
+    fn request(method: Method) -> RequestHead<'static> {
+        Request::builder()
+            .method(method)
+            .uri("/")
+            .body(())
+            .unwrap()
+            .into()
+    }
+
+    #[test]
+    fn in_order() {
+        block_on(async {
+            let mut io = MockIo::new(
+                b"HTTP/1.1 200 OK\r\ncontent-length: 5\r\n\r\nhello\
+                HTTP/1.1 200 OK\r\ncontent-length: 5\r\n\r\n\
+                HTTP/1.1 100 Continue\r\n\r\n\
+                HTTP/1.1 201 Created\r\ncontent-length: 3\r\n\r\nfoo",
+            );
+            let mut pipeline = Pipeline::new(&mut io);
+            for method in [Method::GET, Method::HEAD, Method::GET] {
+                pipeline
+                    .send(request(method), futures::io::empty())
+                    .await
+                    .unwrap();
+            }
+            assert_eq!(pipeline.outstanding(), 3);
+
+            let (head, mut body) = pipeline.next_response().await.unwrap().unwrap();
+            assert_eq!(head.status(), 200);
+            let mut buf = [0u8; 2];
+            body.read_exact(&mut buf).await.unwrap();
+            assert_eq!(&buf, b"he");
+
+            let (head, mut body) = pipeline.next_response().await.unwrap().unwrap();
+            assert_eq!(head.headers()["content-length"], "5");
+            let mut decoded = Vec::new();
+            body.read_to_end(&mut decoded).await.unwrap();
+            assert!(decoded.is_empty());
+
+            let (head, mut body) = pipeline.next_response().await.unwrap().unwrap();
+            assert_eq!(head.status(), 201);
+            body.read_to_end(&mut decoded).await.unwrap();
+            assert_eq!(decoded, b"foo");
+            assert!(pipeline.next_response().await.unwrap().is_none());
+            drop(pipeline);
+            assert_eq!(
+                String::from_utf8(io.output).unwrap(),
+                "GET / HTTP/1.1\r\n\r\nHEAD / HTTP/1.1\r\n\r\nGET / HTTP/1.1\r\n\r\n"
+            );
+        })
+    }
+
+    #[test]
+    fn early_close() {
+        block_on(async {
+            let mut io = MockIo::new(
+                b"HTTP/1.1 200 OK\r\nconnection: close\r\ncontent-length: 5\r\n\r\nhello",
+            );
+            let mut pipeline = Pipeline::new(&mut io);
+            for _ in 0..3 {
+                pipeline
+                    .send(request(Method::GET), futures::io::empty())
+                    .await
+                    .unwrap();
+            }
+            let (head, _) = pipeline.next_response().await.unwrap().unwrap();
+            assert_eq!(head.status(), 200);
+            let err = pipeline
+                .send(request(Method::GET), futures::io::empty())
+                .await;
+            assert!(err.is_err());
+            for _ in 0..2 {
+                let err = pipeline.next_response().await.err().unwrap();
+                let err = err.downcast::<io::Error>().unwrap();
+                assert_eq!(err.kind(), io::ErrorKind::ConnectionAborted);
+            }
+            assert!(pipeline.next_response().await.unwrap().is_none());
+        })
+    }
+
+    #[test]
+    fn drain_limit() {
+        block_on(async {
+            let mut io = MockIo::new(
+                b"HTTP/1.1 200 OK\r\ncontent-length: 20\r\n\r\nhello world, hello!!\
+                HTTP/1.1 200 OK\r\ncontent-length: 5\r\n\r\nhello",
+            );
+            let mut pipeline = Pipeline::with_drain_limit(&mut io, 10);
+            for _ in 0..2 {
+                pipeline
+                    .send(request(Method::GET), futures::io::empty())
+                    .await
+                    .unwrap();
+            }
+            let (_, mut body) = pipeline.next_response().await.unwrap().unwrap();
+            let mut buf = [0u8; 5];
+            body.read_exact(&mut buf).await.unwrap();
+            let err = pipeline.next_response().await.err().unwrap();
+            let err = err.downcast::<io::Error>().unwrap();
+            assert_eq!(err.kind(), io::ErrorKind::ConnectionAborted);
+            assert!(pipeline
+                .send(request(Method::GET), futures::io::empty())
+                .await
+                .is_err());
+        })
+    }
+}
@@ -0,0 +1,183 @@
+use crate::transaction::client::send;
+use crate::{BodyDecode, BodyDecodeState, RequestHead, ResponseHead};
+use anyhow::bail;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use futures::prelude::*;
+use http::header::{HOST, PROXY_AUTHORIZATION};
+use http::uri::{Authority, Scheme};
+use http::{HeaderMap, HeaderValue, Method, Uri, Version};
+use std::borrow::Cow;
+
+/// `Proxy-Authorization` value for the basic authentication scheme.
+pub fn basic_auth(user: &str, password: &str) -> HeaderValue {
+    let credentials = STANDARD.encode(format!("{}:{}", user, password));
+    HeaderValue::from_str(&format!("Basic {}", credentials)).unwrap()
+}
+
+/// Prepares a request to be sent through a forward proxy.
+///
+/// The target is turned into absolute-form, taking the authority from the `Host` header for
+/// origin-form targets, and `authorization` is set as `Proxy-Authorization`.
+pub fn proxy_request(
+    head: &mut RequestHead,
+    authorization: Option<HeaderValue>,
+) -> anyhow::Result<()> {
+    let uri = head.uri();
+    let authority = match (uri.authority(), head.headers().get(HOST)) {
+        (Some(authority), _) => authority.clone(),
+        (None, Some(host)) => Authority::try_from(host.as_bytes())?,
+        (None, None) => bail!("request has no target authority"),
+    };
+    let mut parts = uri.clone().into_parts();
+    parts.scheme.get_or_insert(Scheme::HTTP);
+    parts
+        .path_and_query
+        .get_or_insert_with(|| "/".parse().unwrap());
+    parts.authority = Some(authority.clone());
+    *head.uri_mut() = Uri::from_parts(parts)?;
+    let headers = head.headers_mut();
+    if !headers.contains_key(HOST) {
+        headers.insert(HOST, HeaderValue::from_str(authority.as_str())?);
+    }
+    if let Some(authorization) = authorization {
+        headers.insert(PROXY_AUTHORIZATION, authorization);
+    }
+    Ok(())
+}
+
+/// Outcome of a `CONNECT` request to a proxy.
+pub enum ConnectResponse<IO: AsyncRead + Unpin> {
+    /// The proxy opened the tunnel, the transport now carries the tunneled connection.
+    Established(ResponseHead<'static>, IO),
+    /// The proxy refused the tunnel with a regular response.
+    Refused(ResponseHead<'static>, BodyDecode<BodyDecodeState, IO>),
+}
+
+/// Asks a proxy to open a tunnel to `authority`, sending `headers` like `Proxy-Authorization`
+/// along with the `CONNECT` request.
+pub async fn connect<IO: AsyncRead + AsyncWrite + Unpin>(
+    io: IO,
+    authority: &Authority,
+    mut headers: HeaderMap,
+) -> anyhow::Result<ConnectResponse<IO>> {
+    headers.insert(HOST, HeaderValue::from_str(authority.as_str())?);
+    let uri = Uri::from(authority.clone());
+    let head = RequestHead::new(
+        Method::CONNECT,
+        Cow::Owned(uri),
+        Version::HTTP_11,
+        Cow::Owned(headers),
+    );
+    let (response, body) = send(io, head, futures::io::empty()).await?;
+    if !response.status().is_success() {
+        return Ok(ConnectResponse::Refused(response, body));
+    }
+    let (_, io) = body.into_inner();
+    Ok(ConnectResponse::Established(response, io))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::client::{basic_auth, connect, proxy_request, send, ConnectResponse};
+    use crate::internal::io_future::IoFutureWithOutputState;
+    use crate::{respond_connect, RequestHead, ResponseHead};
+    use futures::prelude::*;
+    use http::header::PROXY_AUTHORIZATION;
+    use http::uri::Authority;
+    use http::{HeaderMap, Request, Response, StatusCode, Version};
+    use smol::net::{TcpListener, TcpStream};
+    use std::borrow::Cow;
+
+    /// Accepts one connection, answers plain requests with their target and tunnels to an
+    /// upstream that answers "ping" with "pong".
+    async fn proxy(listener: TcpListener) -> anyhow::Result<()> {
+        let (mut io, _) = listener.accept().await?;
+        let (_, request) = RequestHead::decode_state().into_future(&mut io).await?;
+        let authorized =
+            request.headers().get(PROXY_AUTHORIZATION) == Some(&basic_auth("user", "secret"));
+        let status = match authorized {
+            true => StatusCode::OK,
+            false => StatusCode::PROXY_AUTHENTICATION_REQUIRED,
+        };
+        if request.connect_authority().is_some() {
+            let head = ResponseHead::new(status, Version::HTTP_11, Cow::Owned(HeaderMap::new()));
+            let io = respond_connect(&mut io, &request, head).await?;
+            if authorized {
+                let mut ping = [0u8; 4];
+                io.read_exact(&mut ping).await?;
+                assert_eq!(&ping, b"ping");
+                io.write_all(b"pong").await?;
+            }
+        } else {
+            let mut response = Response::new(request.uri().to_string());
+            *response.status_mut() = status;
+            crate::encode_response(&mut io, &response, &request.method(), request.version())
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn bind() -> (TcpListener, TcpStream) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let io = TcpStream::connect(listener.local_addr().unwrap())
+            .await
+            .unwrap();
+        (listener, io)
+    }
+
+    #[test]
+    fn absolute_form() {
+        smol::block_on(async {
+            let (listener, io) = bind().await;
+            let client = async {
+                let request = Request::get("/index.html")
+                    .header("host", "example.com")
+                    .body(())
+                    .unwrap();
+                let mut head = RequestHead::from(request);
+                proxy_request(&mut head, Some(basic_auth("user", "secret"))).unwrap();
+                let (response, mut body) = send(io, head, futures::io::empty()).await.unwrap();
+                assert_eq!(response.status(), 200);
+                let mut target = String::new();
+                body.read_to_string(&mut target).await.unwrap();
+                assert_eq!(target, "http://example.com/index.html");
+            };
+            let (proxied, ()) = future::join(proxy(listener), client).await;
+            proxied.unwrap();
+        })
+    }
+
+    #[test]
+    fn tunnel() {
+        smol::block_on(async {
+            let (listener, io) = bind().await;
+            let client = async {
+                let authority = Authority::from_static("example.com:443");
+                let mut headers = HeaderMap::new();
+                headers.insert(PROXY_AUTHORIZATION, basic_auth("user", "secret"));
+                let mut io = match connect(io, &authority, headers).await.unwrap() {
+                    ConnectResponse::Established(_, io) => io,
+                    ConnectResponse::Refused(head, _) => panic!("refused with {}", head.status()),
+                };
+                io.write_all(b"ping").await.unwrap();
+                let mut pong = String::new();
+                io.read_to_string(&mut pong).await.unwrap();
+                assert_eq!(pong, "pong");
+            };
+            let (proxied, ()) = future::join(proxy(listener), client).await;
+            proxied.unwrap();
+
+            let (listener, io) = bind().await;
+            let client = async {
+                let authority = Authority::from_static("example.com:443");
+                match connect(io, &authority, HeaderMap::new()).await.unwrap() {
+                    ConnectResponse::Refused(head, _) => assert_eq!(head.status(), 407),
+                    ConnectResponse::Established(..) => panic!("unexpected tunnel"),
+                }
+            };
+            let (proxied, ()) = future::join(proxy(listener), client).await;
+            proxied.unwrap();
+        })
+    }
+}
@@ -0,0 +1,107 @@
+use crate::{BodyDecode, BodyDecodeState, BodyEncodeState, RequestHead, ResponseHead};
+use futures::prelude::*;
+use http::header::{CONTENT_LENGTH, TRANSFER_ENCODING};
+use http::{Method, Version};
+use std::io;
+
+/// Sends a request and decodes the response head.
+///
+/// The request body is framed according to the framing headers of `head`, or chunked if there
+/// are none. Requests with methods that don't expect content, like `GET`, are sent without body
+/// unless `head` declares one.
+/// The returned body decoder yields the response body and hands back the transport via
+/// [`BodyDecode::into_transport`] once the response has been consumed.
+pub async fn send<IO: AsyncRead + AsyncWrite + Unpin, B: AsyncRead + Unpin>(
+    mut io: IO,
+    mut head: RequestHead<'_>,
+    body: B,
+) -> anyhow::Result<(ResponseHead<'static>, BodyDecode<BodyDecodeState, IO>)> {
+    let state = body_state(&mut head)?;
+    head.encode(&mut io).await?;
+    send_body(&mut io, state, body).await?;
+
+    let (io, response) = ResponseHead::decode_final(io, |_| {}).await?;
+    let body = BodyDecodeState::from_response_head(&response, &head.method())?;
+    Ok((response, body.into_async_read(io)))
+}
+
+pub(crate) fn body_state(head: &mut RequestHead) -> anyhow::Result<BodyEncodeState> {
+    let length = match declares_body(head) {
+        true => None,
+        false => Some(0),
+    };
+    BodyEncodeState::for_request(head, length, Version::HTTP_11)
+}
+
+pub(crate) async fn send_body<IO: AsyncWrite + Unpin, B: AsyncRead + Unpin>(
+    io: IO,
+    state: BodyEncodeState,
+    body: B,
+) -> io::Result<()> {
+    let mut encode = state.into_async_write(io);
+    futures::io::copy(body, &mut encode).await?;
+    encode.finish().await
+}
+
+fn declares_body(head: &RequestHead) -> bool {
+    let headers = head.headers();
+    headers.contains_key(CONTENT_LENGTH)
+        || headers.contains_key(TRANSFER_ENCODING)
+        || matches!(head.method(), Method::POST | Method::PUT | Method::PATCH)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::client::send;
+    use crate::internal::mock_io::MockIo;
+    use crate::RequestHead;
+    use futures::executor::block_on;
+    use futures::prelude::*;
+    use http::Request;
+
+    const RESPONSE: &[u8] = b"HTTP/1.1 200 OK\r\ncontent-length: 6\r\n\r\nhello HTTP/1.1";
+
+    #[test]
+    fn post() {
+        block_on(async {
+            let request = Request::post("/echo").body(()).unwrap();
+            let mut io = MockIo::new(RESPONSE);
+            let (head, mut body) = send(
+                &mut io,
+                RequestHead::ref_request(&request),
+                &b"hello world!"[..],
+            )
+            .await
+            .unwrap();
+            assert_eq!(head.status(), 200);
+            let mut decoded = String::new();
+            body.read_to_string(&mut decoded).await.unwrap();
+            assert_eq!(decoded, "hello ");
+            let io = body.into_transport().ok().unwrap();
+            assert_eq!(io.remaining_input(), b"HTTP/1.1");
+            assert_eq!(
+                String::from_utf8(io.output.clone()).unwrap(),
+                "POST /echo HTTP/1.1\r\ntransfer-encoding: chunked\r\n\r\nC\r\nhello world!\r\n0\r\n\r\n"
+            );
+        })
+    }
+
+    #[test]
+    fn head() {
+        block_on(async {
+            let request = Request::head("/").body(()).unwrap();
+            let mut io = MockIo::new(RESPONSE);
+            let (head, body) = send(
+                &mut io,
+                RequestHead::ref_request(&request),
+                futures::io::empty(),
+            )
+            .await
+            .unwrap();
+            assert_eq!(head.status(), 200);
+            let io = body.into_transport().ok().unwrap();
+            assert_eq!(io.remaining_input(), b"hello HTTP/1.1");
+            assert_eq!(io.output, b"HEAD / HTTP/1.1\r\n\r\n");
+        })
+    }
+}
@@ -0,0 +1,156 @@
+use crate::internal::io_future::IoFutureWithOutputState;
+use crate::response::head::interim::is_interim;
+use crate::transaction::client::send::{body_state, send_body};
+use crate::{BodyDecode, BodyDecodeState, RequestHead, ResponseHead};
+use futures::prelude::*;
+use futures::{pin_mut, ready};
+use http::header::EXPECT;
+use http::{HeaderValue, StatusCode};
+use std::task::Poll;
+
+/// Like [`send`](crate::client::send), but sends `Expect: 100-continue` and holds the body
+/// back until the server responds with `100 Continue` or `timeout` completes, whichever
+/// happens first. The timeout future makes this independent of any particular runtime.
+///
+/// If the server responds with a final status before that, the body is never sent.
+/// The returned flag tells whether the body was sent. If it wasn't, the server may still
+/// expect it, so the connection must not be reused for further requests.
+pub async fn send_with_continue<IO, B, T>(
+    mut io: IO,
+    mut head: RequestHead<'_>,
+    body: B,
+    timeout: T,
+) -> anyhow::Result<(ResponseHead<'static>, BodyDecode<BodyDecodeState, IO>, bool)>
+where
+    IO: AsyncRead + AsyncWrite + Unpin,
+    B: AsyncRead + Unpin,
+    T: Future,
+{
+    head.headers_mut()
+        .insert(EXPECT, HeaderValue::from_static("100-continue"));
+    let state = body_state(&mut head)?;
+    head.encode(&mut io).await?;
+    io.flush().await?;
+
+    pin_mut!(timeout);
+    let mut timed_out = false;
+    let mut decode = ResponseHead::decode_state();
+    let response = loop {
+        let response = future::poll_fn(|cx| {
+            if let Poll::Ready(response) = decode.poll(cx, &mut io) {
+                return Poll::Ready(Some(response));
+            }
+            if !timed_out {
+                ready!(timeout.as_mut().poll(cx));
+                timed_out = true;
+                return Poll::Ready(None);
+            }
+            Poll::Pending
+        })
+        .await;
+        match response {
+            None => break None,
+            Some(response) => {
+                let response = response?;
+                decode = ResponseHead::decode_state();
+                match response.status() {
+                    StatusCode::CONTINUE => break None,
+                    status if is_interim(status) => continue,
+                    _ => break Some(response),
+                }
+            }
+        }
+    };
+    let (response, sent) = match response {
+        Some(response) => (response, false),
+        None => {
+            send_body(&mut io, state, body).await?;
+            let mut response = decode.into_future(&mut io).await?.1;
+            if is_interim(response.status()) {
+                response = ResponseHead::decode_final(&mut io, |_| {}).await?.1;
+            }
+            (response, true)
+        }
+    };
+    let body = BodyDecodeState::from_response_head(&response, &head.method())?;
+    Ok((response, body.into_async_read(io), sent))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::client::send_with_continue;
+    use crate::internal::mock_io::MockIo;
+    use crate::RequestHead;
+    use futures::executor::block_on;
+    use futures::future;
+    use http::Request;
+
+    const REQUEST_HEAD: &str =
+        "PUT / HTTP/1.1\r\nexpect: 100-continue\r\ncontent-length: 5\r\n\r\n";
+
+    fn request() -> Request<()> {
+        Request::put("/")
+            .header("content-length", "5")
+            .body(())
+            .unwrap()
+    }
+
+    #[test]
+    fn continued() {
+        block_on(async {
+            let mut io =
+                MockIo::new(b"HTTP/1.1 100 Continue\r\n\r\nHTTP/1.1 204 No Content\r\n\r\n");
+            let request = request();
+            let (head, _, sent) = send_with_continue(
+                &mut io,
+                RequestHead::ref_request(&request),
+                &b"hello"[..],
+                future::pending::<()>(),
+            )
+            .await
+            .unwrap();
+            assert_eq!(head.status(), 204);
+            assert!(sent);
+            assert_eq!(io.output, format!("{}hello", REQUEST_HEAD).as_bytes());
+        })
+    }
+
+    #[test]
+    fn rejected() {
+        block_on(async {
+            let mut io =
+                MockIo::new(b"HTTP/1.1 413 Payload Too Large\r\ncontent-length: 0\r\n\r\n");
+            let request = request();
+            let (head, _, sent) = send_with_continue(
+                &mut io,
+                RequestHead::ref_request(&request),
+                &b"hello"[..],
+                future::pending::<()>(),
+            )
+            .await
+            .unwrap();
+            assert_eq!(head.status(), 413);
+            assert!(!sent);
+            assert_eq!(io.output, REQUEST_HEAD.as_bytes());
+        })
+    }
+
+    #[test]
+    fn timeout() {
+        block_on(async {
+            let mut io = MockIo::new(b"HTTP/1.1 204 No Content\r\n\r\n");
+            io.hold_input = REQUEST_HEAD.len() + 5;
+            let request = request();
+            let (head, _, sent) = send_with_continue(
+                &mut io,
+                RequestHead::ref_request(&request),
+                &b"hello"[..],
+                future::ready(()),
+            )
+            .await
+            .unwrap();
+            assert_eq!(head.status(), 204);
+            assert!(sent);
+        })
+    }
+}
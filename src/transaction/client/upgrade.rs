@@ -0,0 +1,104 @@
+use crate::transaction::client::send;
+use crate::{BodyDecode, BodyDecodeState, RequestHead, ResponseHead};
+use futures::prelude::*;
+use http::header::{CONNECTION, UPGRADE};
+use http::{HeaderValue, StatusCode};
+
+/// Outcome of an upgrade request.
+pub enum UpgradeResponse<IO: AsyncRead + Unpin> {
+    /// The server switched to the requested protocol, the transport now carries it.
+    Switched(ResponseHead<'static>, IO),
+    /// The server answered the request without switching protocols.
+    Refused(ResponseHead<'static>, BodyDecode<BodyDecodeState, IO>),
+}
+
+/// Sends a request offering an upgrade to `protocol`, adding the `Connection` and `Upgrade`
+/// headers, and hands back the transport if the server responds with `101 Switching Protocols`.
+///
+/// Bytes sent by the server after the `101` response are left unread in `io`.
+pub async fn upgrade<IO: AsyncRead + AsyncWrite + Unpin, B: AsyncRead + Unpin>(
+    io: IO,
+    mut head: RequestHead<'_>,
+    protocol: &str,
+    body: B,
+) -> anyhow::Result<UpgradeResponse<IO>> {
+    if !head.has_connection_token("upgrade") {
+        head.headers_mut()
+            .append(CONNECTION, HeaderValue::from_static("upgrade"));
+    }
+    head.headers_mut()
+        .insert(UPGRADE, HeaderValue::from_str(protocol)?);
+    let (response, body) = send(io, head, body).await?;
+    if response.status() != StatusCode::SWITCHING_PROTOCOLS {
+        return Ok(UpgradeResponse::Refused(response, body));
+    }
+    let (_, io) = body.into_inner();
+    Ok(UpgradeResponse::Switched(response, io))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::client::{upgrade, UpgradeResponse};
+    use crate::internal::mock_io::MockIo;
+    use crate::RequestHead;
+    use futures::executor::block_on;
+    use futures::prelude::*;
+    use http::Request;
+
+    #[test]
+    fn switched() {
+        block_on(async {
+            let request = Request::get("/chat").body(()).unwrap();
+            let mut io = MockIo::new(
+                b"HTTP/1.1 101 Switching Protocols\r\nconnection: upgrade\r\nupgrade: foo\r\n\r\nraw",
+            );
+            let response = upgrade(
+                &mut io,
+                RequestHead::ref_request(&request),
+                "foo",
+                futures::io::empty(),
+            )
+            .await
+            .unwrap();
+            let transport = match response {
+                UpgradeResponse::Switched(head, transport) => {
+                    assert_eq!(head.headers()["upgrade"], "foo");
+                    transport
+                }
+                UpgradeResponse::Refused(..) => panic!("upgrade refused"),
+            };
+            let mut raw = String::new();
+            transport.read_to_string(&mut raw).await.unwrap();
+            assert_eq!(raw, "raw");
+            assert_eq!(
+                String::from_utf8(io.output).unwrap(),
+                "GET /chat HTTP/1.1\r\nconnection: upgrade\r\nupgrade: foo\r\n\r\n"
+            );
+        })
+    }
+
+    #[test]
+    fn refused() {
+        block_on(async {
+            let request = Request::get("/chat").body(()).unwrap();
+            let io = MockIo::new(b"HTTP/1.1 200 OK\r\ncontent-length: 2\r\n\r\nno");
+            let response = upgrade(
+                io,
+                RequestHead::ref_request(&request),
+                "foo",
+                futures::io::empty(),
+            )
+            .await
+            .unwrap();
+            match response {
+                UpgradeResponse::Refused(head, mut body) => {
+                    assert_eq!(head.status(), 200);
+                    let mut decoded = String::new();
+                    body.read_to_string(&mut decoded).await.unwrap();
+                    assert_eq!(decoded, "no");
+                }
+                UpgradeResponse::Switched(..) => panic!("unexpected upgrade"),
+            }
+        })
+    }
+}
@@ -0,0 +1,26 @@
+use futures::prelude::*;
+use std::io;
+
+/// Copies data in both directions between `a` and `b` until both directions reached EOF and
+/// returns the number of bytes copied from `a` to `b` and from `b` to `a`.
+///
+/// When one side reaches EOF, the other side is closed for writing, while the opposite
+/// direction keeps running.
+pub async fn copy_bidirectional<A, B>(a: A, b: B) -> io::Result<(u64, u64)>
+where
+    A: AsyncRead + AsyncWrite + Unpin,
+    B: AsyncRead + AsyncWrite + Unpin,
+{
+    let (a_read, a_write) = a.split();
+    let (b_read, b_write) = b.split();
+    future::try_join(copy_half(a_read, b_write), copy_half(b_read, a_write)).await
+}
+
+async fn copy_half<R: AsyncRead + Unpin, W: AsyncWrite + Unpin>(
+    reader: R,
+    mut writer: W,
+) -> io::Result<u64> {
+    let copied = futures::io::copy(reader, &mut writer).await?;
+    writer.close().await?;
+    Ok(copied)
+}
@@ -1,3 +1,8 @@
+pub mod client;
+mod copy;
+mod read_buffer;
 mod server;
 
+pub use copy::*;
+pub use read_buffer::*;
 pub use server::*;
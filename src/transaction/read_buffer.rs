@@ -0,0 +1,135 @@
+use futures::prelude::*;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// Buffers reads from a connection, so that the small reads of the head decoders don't each
+/// hit the transport. Bytes read ahead stay available for the next message on the connection.
+///
+/// Writes are passed through to the transport.
+pub struct ReadBuffer<IO> {
+    io: IO,
+    buffer: Vec<u8>,
+    start: usize,
+    end: usize,
+}
+
+impl<IO> ReadBuffer<IO> {
+    pub fn new(io: IO) -> Self {
+        Self::with_capacity(8192, io)
+    }
+    pub fn with_capacity(capacity: usize, io: IO) -> Self {
+        Self::from_parts(io, Vec::with_capacity(capacity))
+    }
+    /// Creates a buffer starting with bytes that were already read from `io`.
+    pub fn from_parts(io: IO, buffered: Vec<u8>) -> Self {
+        let mut buffer = buffered;
+        let end = buffer.len();
+        buffer.resize(buffer.capacity().max(8192), 0);
+        Self {
+            io,
+            buffer,
+            start: 0,
+            end,
+        }
+    }
+    /// Bytes read from the transport, but not consumed yet.
+    pub fn buffered(&self) -> &[u8] {
+        &self.buffer[self.start..self.end]
+    }
+    pub fn get_ref(&self) -> &IO {
+        &self.io
+    }
+    pub fn get_mut(&mut self) -> &mut IO {
+        &mut self.io
+    }
+    /// Returns the transport and the bytes read from it, but not consumed yet.
+    pub fn into_parts(mut self) -> (IO, Vec<u8>) {
+        self.buffer.truncate(self.end);
+        self.buffer.drain(..self.start);
+        (self.io, self.buffer)
+    }
+}
+
+impl<IO: AsyncRead + Unpin> AsyncBufRead for ReadBuffer<IO> {
+    fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<&[u8]>> {
+        let this = self.get_mut();
+        if this.start == this.end {
+            match Pin::new(&mut this.io).poll_read(cx, &mut this.buffer) {
+                Poll::Ready(Ok(n)) => (this.start, this.end) = (0, n),
+                Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+        Poll::Ready(Ok(&this.buffer[this.start..this.end]))
+    }
+    fn consume(mut self: Pin<&mut Self>, amt: usize) {
+        self.start = (self.start + amt).min(self.end);
+    }
+}
+
+impl<IO: AsyncRead + Unpin> AsyncRead for ReadBuffer<IO> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        if self.start == self.end && buf.len() >= self.buffer.len() {
+            return Pin::new(&mut self.io).poll_read(cx, buf);
+        }
+        let n = match self.as_mut().poll_fill_buf(cx) {
+            Poll::Ready(Ok(buffered)) => {
+                let n = buffered.len().min(buf.len());
+                buf[..n].copy_from_slice(&buffered[..n]);
+                n
+            }
+            Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+            Poll::Pending => return Poll::Pending,
+        };
+        self.consume(n);
+        Poll::Ready(Ok(n))
+    }
+}
+
+impl<IO: AsyncWrite + Unpin> AsyncWrite for ReadBuffer<IO> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.io).poll_write(cx, buf)
+    }
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.io).poll_flush(cx)
+    }
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.io).poll_close(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::internal::io_future::IoFutureWithOutputState;
+    use crate::{ReadBuffer, RequestHead};
+    use futures::executor::block_on;
+    use futures::io::Cursor;
+
+    #[test]
+    fn heads_in_one_read() {
+        block_on(async {
+            let input = b"GET /a HTTP/1.1\r\n\r\nGET /b HTTP/1.1\r\n\r\nrest".to_vec();
+            let mut io = ReadBuffer::new(Cursor::new(input));
+            for path in ["/a", "/b"] {
+                let (_, head) = RequestHead::decode_state()
+                    .into_future(&mut io)
+                    .await
+                    .unwrap();
+                assert_eq!(head.uri(), path);
+            }
+            assert_eq!(io.buffered(), b"rest");
+            let (io, buffered) = io.into_parts();
+            assert_eq!(io.position(), 42);
+            assert_eq!(buffered, b"rest");
+        })
+    }
+}
@@ -1,26 +1,53 @@
 use crate::common::length_from_headers;
 use crate::internal::buffer_write::BufferWriteState;
 use crate::internal::io_future::IoFutureState;
-use crate::{BodyDecodeState, RequestHead, ResponseHead};
+use crate::{BodyDecodeState, Drained, RequestHead, ResponseHead};
 use futures::prelude::*;
-use http::header::EXPECT;
-use http::{HeaderMap, StatusCode, Version};
+use futures::ready;
+use http::header::{CONNECTION, EXPECT};
+use http::{HeaderMap, HeaderValue, StatusCode, Version};
 use std::borrow::{BorrowMut, Cow};
+use std::future::Future;
 use std::io;
 use std::pin::Pin;
 use std::task::{Context, Poll};
 
+/// Expectation of a request, as declared by its `Expect` header.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Expectation {
+    None,
+    Continue,
+    /// The request expects something other than `100-continue`, which should be answered
+    /// with `417 Expectation Failed`.
+    Unsupported,
+}
+
 pub struct BodyDecodeWithContinueState {
     cont: Option<BufferWriteState>,
     flushed_cont: bool,
+    expectation: Expectation,
+    rejected: bool,
     body: BodyDecodeState,
 }
 
 impl BodyDecodeWithContinueState {
     pub fn from_head(head: &RequestHead) -> anyhow::Result<Self> {
-        Ok(Self::from_headers(head.headers(), head.version())?)
+        Self::from_headers(head.headers(), head.version())
     }
     pub fn new(version: Version, length: Option<u64>, send_continue: bool) -> Self {
+        let expectation = match send_continue {
+            true => Expectation::Continue,
+            false => Expectation::None,
+        };
+        Self::with_expectation(version, length, expectation)
+    }
+    /// `100 Continue` is only sent for [`Expectation::Continue`] and never to HTTP/1.0 clients.
+    pub fn with_expectation(
+        version: Version,
+        length: Option<u64>,
+        expectation: Expectation,
+    ) -> Self {
+        let send_continue = expectation == Expectation::Continue && version >= Version::HTTP_11;
         Self {
             cont: match send_continue {
                 true => Some(
@@ -30,6 +57,8 @@ impl BodyDecodeWithContinueState {
                 false => None,
             },
             flushed_cont: false,
+            expectation,
+            rejected: false,
             body: BodyDecodeState::new(length),
         }
     }
@@ -37,23 +66,133 @@ impl BodyDecodeWithContinueState {
         headers: &http::header::HeaderMap,
         version: Version,
     ) -> anyhow::Result<Self> {
-        Ok(Self::new(
+        Ok(Self::with_expectation(
             version,
             length_from_headers(headers)?,
-            contains_continue(headers),
+            expectation_from_headers(headers),
         ))
     }
     pub fn into_async_read<IO: AsyncRead + AsyncWrite + Unpin>(
         self,
         io: IO,
     ) -> BodyDecodeWithContinue<Self, IO> {
-        BodyDecodeWithContinue { io, state: self }
+        BodyDecodeWithContinue {
+            io,
+            state: self,
+            #[cfg(feature = "http-body")]
+            buffer: bytes::BytesMut::new(),
+        }
     }
     pub fn as_async_read<IO: AsyncRead + AsyncWrite + Unpin>(
         &mut self,
         io: IO,
     ) -> BodyDecodeWithContinue<&mut Self, IO> {
-        BodyDecodeWithContinue { io, state: self }
+        BodyDecodeWithContinue {
+            io,
+            state: self,
+            #[cfg(feature = "http-body")]
+            buffer: bytes::BytesMut::new(),
+        }
+    }
+    pub fn body(&self) -> &BodyDecodeState {
+        &self.body
+    }
+    #[cfg(feature = "http-body")]
+    pub(crate) fn body_mut(&mut self) -> &mut BodyDecodeState {
+        &mut self.body
+    }
+    pub fn expectation(&self) -> Expectation {
+        self.expectation
+    }
+    /// True until `100 Continue` was sent or the body was rejected.
+    pub fn is_continue_pending(&self) -> bool {
+        self.cont.is_some()
+    }
+    pub fn is_rejected(&self) -> bool {
+        self.rejected
+    }
+    /// Status a request with this body should be rejected with: `417 Expectation Failed` for
+    /// unsupported expectations and `413 Payload Too Large` if the declared length exceeds
+    /// `max_length`.
+    pub fn validate(&self, max_length: u64) -> Result<(), StatusCode> {
+        if self.expectation == Expectation::Unsupported {
+            return Err(StatusCode::EXPECTATION_FAILED);
+        }
+        match self.body.remaining() {
+            Some(remaining) if remaining > max_length => Err(StatusCode::PAYLOAD_TOO_LARGE),
+            _ => Ok(()),
+        }
+    }
+    /// Sends `100 Continue` if it is pending. Reading the body does the same implicitly.
+    pub fn poll_accept<IO: AsyncWrite + Unpin>(
+        &mut self,
+        cx: &mut Context<'_>,
+        io: &mut IO,
+    ) -> Poll<io::Result<()>> {
+        if let Some(cont) = &mut self.cont {
+            match cont.poll(cx, io) {
+                Poll::Ready(Ok(())) => self.cont.take(),
+                Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+                Poll::Pending => return Poll::Pending,
+            };
+        }
+        if !self.flushed_cont {
+            match Pin::new(&mut *io).poll_flush(cx) {
+                Poll::Ready(Ok(())) => self.flushed_cont = true,
+                Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+        Poll::Ready(Ok(()))
+    }
+    /// Rejects the body. `100 Continue` won't be sent and the body can't be read. If part of
+    /// `100 Continue` was written already, it is completed first, so that the response
+    /// following it stays intact. Unless the body is empty, the connection must close after
+    /// the response, since the client may or may not send the body.
+    pub fn poll_reject<IO: AsyncWrite + Unpin>(
+        &mut self,
+        cx: &mut Context<'_>,
+        io: &mut IO,
+    ) -> Poll<io::Result<()>> {
+        if matches!(&self.cont, Some(cont) if !cont.is_started()) {
+            self.cont = None;
+        }
+        ready!(self.poll_accept(cx, io))?;
+        self.rejected = true;
+        Poll::Ready(Ok(()))
+    }
+    /// Encodes the final response rejecting the body, adding `Connection: close` unless the
+    /// body is empty.
+    pub(crate) fn reject_response(&self, mut head: ResponseHead<'_>) -> BufferWriteState {
+        if !self.body.is_done() {
+            head.headers_mut()
+                .insert(CONNECTION, HeaderValue::from_static("close"));
+        }
+        head.encode_state()
+    }
+    /// Like [`poll_reject`](Self::poll_reject), then writes and flushes `response`.
+    pub(crate) fn poll_reject_with<IO: AsyncWrite + Unpin>(
+        &mut self,
+        cx: &mut Context<'_>,
+        io: &mut IO,
+        response: &mut BufferWriteState,
+    ) -> Poll<io::Result<()>> {
+        ready!(self.poll_reject(cx, io))?;
+        ready!(response.poll(cx, io))?;
+        Pin::new(io).poll_flush(cx)
+    }
+    /// Like [`BodyDecodeState::poll_drain`]. If `100 Continue` was not sent yet, the client may
+    /// or may not send the body, so the connection must close unless the body is empty.
+    pub fn poll_drain<IO: AsyncRead + AsyncWrite + Unpin>(
+        &mut self,
+        cx: &mut Context<'_>,
+        io: &mut IO,
+        limit: &mut u64,
+    ) -> Poll<io::Result<Drained>> {
+        if (self.cont.is_some() || self.rejected) && !self.body.is_done() {
+            return Poll::Ready(Ok(Drained::MustClose));
+        }
+        self.body.poll_drain(io, cx, limit)
     }
     pub fn poll_read<IO: AsyncRead + AsyncWrite + Unpin>(
         &mut self,
@@ -61,22 +200,16 @@ impl BodyDecodeWithContinueState {
         buf: &mut [u8],
         io: &mut IO,
     ) -> Poll<io::Result<usize>> {
-        loop {
-            if let Some(cont) = &mut self.cont {
-                match cont.poll(cx, io) {
-                    Poll::Ready(Ok(())) => self.cont.take(),
-                    Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
-                    Poll::Pending => return Poll::Pending,
-                };
-            }
-            if !self.flushed_cont {
-                match Pin::new(&mut *io).poll_flush(cx) {
-                    Poll::Ready(Ok(())) => self.flushed_cont = true,
-                    Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
-                    Poll::Pending => return Poll::Pending,
-                }
-            }
-            return self.body.poll_read(io, cx, buf);
+        if self.rejected && !self.body.is_done() {
+            return Poll::Ready(Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "request body was rejected",
+            )));
+        }
+        match self.poll_accept(cx, io) {
+            Poll::Ready(Ok(())) => self.body.poll_read(io, cx, buf),
+            Poll::Ready(Err(err)) => Poll::Ready(Err(err)),
+            Poll::Pending => Poll::Pending,
         }
     }
 }
@@ -87,6 +220,9 @@ pub struct BodyDecodeWithContinue<
 > {
     io: IO,
     state: T,
+    /// Read buffer of the [`http_body::Body`] implementation, kept across frames.
+    #[cfg(feature = "http-body")]
+    buffer: bytes::BytesMut,
 }
 
 impl<IO: AsyncRead + AsyncWrite + Unpin> BodyDecodeWithContinue<BodyDecodeWithContinueState, IO> {
@@ -111,6 +247,40 @@ impl<T: BorrowMut<BodyDecodeWithContinueState> + Unpin, IO: AsyncRead + AsyncWri
     pub fn into_inner(self) -> (T, IO) {
         (self.state, self.io)
     }
+    pub fn state(&self) -> &BodyDecodeWithContinueState {
+        self.state.borrow()
+    }
+    #[cfg(feature = "http-body")]
+    pub(crate) fn state_mut(&mut self) -> &mut BodyDecodeWithContinueState {
+        self.state.borrow_mut()
+    }
+    #[cfg(feature = "http-body")]
+    pub(crate) fn buffer_mut(&mut self) -> &mut bytes::BytesMut {
+        &mut self.buffer
+    }
+    /// Discards the rest of the body, reading at most `limit` body bytes.
+    pub fn drain(&mut self, mut limit: u64) -> impl Future<Output = io::Result<Drained>> + '_ {
+        future::poll_fn(move |cx| {
+            self.state
+                .borrow_mut()
+                .poll_drain(cx, &mut self.io, &mut limit)
+        })
+    }
+    /// Sends `100 Continue` now if it is pending.
+    pub fn accept(&mut self) -> impl Future<Output = io::Result<()>> + '_ {
+        future::poll_fn(move |cx| self.state.borrow_mut().poll_accept(cx, &mut self.io))
+    }
+    /// Rejects the body (see [`BodyDecodeWithContinueState::poll_reject`]) and sends the final
+    /// response head. `Connection: close` is added to the head unless the body is empty.
+    /// A response body declared by the head must be written by the caller.
+    pub fn reject(&mut self, head: ResponseHead<'_>) -> impl Future<Output = io::Result<()>> + '_ {
+        let mut response = self.state.borrow().reject_response(head);
+        future::poll_fn(move |cx| {
+            self.state
+                .borrow_mut()
+                .poll_reject_with(cx, &mut self.io, &mut response)
+        })
+    }
 }
 
 impl<T: BorrowMut<BodyDecodeWithContinueState> + Unpin, IO: AsyncRead + AsyncWrite + Unpin>
@@ -128,10 +298,139 @@ impl<T: BorrowMut<BodyDecodeWithContinueState> + Unpin, IO: AsyncRead + AsyncWri
     }
 }
 
-pub(crate) fn contains_continue(headers: &HeaderMap) -> bool {
-    headers
-        .get_all(EXPECT)
-        .iter()
-        .find(|v| v == &"100-continue")
-        .is_some()
+pub(crate) fn expectation_from_headers(headers: &HeaderMap) -> Expectation {
+    let mut expectation = Expectation::None;
+    for v in headers.get_all(EXPECT) {
+        match v.as_bytes().eq_ignore_ascii_case(b"100-continue") {
+            true => expectation = Expectation::Continue,
+            false => return Expectation::Unsupported,
+        }
+    }
+    expectation
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::internal::mock_io::MockIo;
+    use crate::{
+        BodyDecodeWithContinue, BodyDecodeWithContinueState, Drained, Expectation, RequestHead,
+        ResponseHead,
+    };
+    use futures::executor::block_on;
+    use futures::io::Cursor;
+    use futures::prelude::*;
+    use futures::task::noop_waker_ref;
+    use http::{HeaderMap, StatusCode, Version};
+    use std::borrow::Cow;
+    use std::task::Context;
+
+    async fn decode(head: &[u8], body: &[u8]) -> (RequestHead<'static>, MockIo) {
+        let (_, head) = RequestHead::decode(Cursor::new(head)).await.unwrap();
+        (head, MockIo::new(body))
+    }
+
+    #[test]
+    fn continue_on_read() {
+        block_on(async {
+            let (head, mut io) = decode(
+                b"PUT / HTTP/1.1\r\ncontent-length: 5\r\nexpect: 100-continue\r\n\r\n",
+                b"hello",
+            )
+            .await;
+            let mut body = BodyDecodeWithContinue::from_head(&head, &mut io).unwrap();
+            assert_eq!(body.state().expectation(), Expectation::Continue);
+            assert!(body.state().is_continue_pending());
+            let mut decoded = String::new();
+            body.read_to_string(&mut decoded).await.unwrap();
+            assert_eq!(decoded, "hello");
+            assert_eq!(io.output, b"HTTP/1.1 100 Continue\r\n\r\n");
+        })
+    }
+
+    #[test]
+    fn no_continue_for_http10() {
+        block_on(async {
+            let (head, mut io) = decode(
+                b"PUT / HTTP/1.0\r\ncontent-length: 5\r\nexpect: 100-continue\r\n\r\n",
+                b"hello",
+            )
+            .await;
+            let mut body = BodyDecodeWithContinue::from_head(&head, &mut io).unwrap();
+            assert!(!body.state().is_continue_pending());
+            body.read_to_end(&mut Vec::new()).await.unwrap();
+            assert!(io.output.is_empty());
+        })
+    }
+
+    #[test]
+    fn reject() {
+        block_on(async {
+            let (head, mut io) = decode(
+                b"PUT / HTTP/1.1\r\ncontent-length: 5\r\nexpect: 100-continue\r\n\r\n",
+                b"hello",
+            )
+            .await;
+            let mut body = BodyDecodeWithContinue::from_head(&head, &mut io).unwrap();
+            assert_eq!(body.state().validate(4), Err(StatusCode::PAYLOAD_TOO_LARGE));
+            let response = ResponseHead::new(
+                StatusCode::PAYLOAD_TOO_LARGE,
+                Version::HTTP_11,
+                Cow::Owned(HeaderMap::new()),
+            );
+            body.reject(response).await.unwrap();
+            assert!(body.read(&mut [0u8; 5]).await.is_err());
+            assert_eq!(body.drain(1024).await.unwrap(), Drained::MustClose);
+            assert_eq!(
+                io.output,
+                b"HTTP/1.1 413 Payload Too Large\r\nconnection: close\r\n\r\n"
+            );
+        })
+    }
+
+    #[test]
+    fn reject_after_partial_continue() {
+        block_on(async {
+            let (head, mut io) = decode(
+                b"PUT / HTTP/1.1\r\ncontent-length: 5\r\nexpect: 100-continue\r\n\r\n",
+                b"hello",
+            )
+            .await;
+            io.hold_output = Some(10);
+            let mut state = BodyDecodeWithContinueState::from_head(&head).unwrap();
+            let mut cx = Context::from_waker(noop_waker_ref());
+            assert!(state.poll_accept(&mut cx, &mut io).is_pending());
+            io.hold_output = None;
+            let response = ResponseHead::new(
+                StatusCode::PAYLOAD_TOO_LARGE,
+                Version::HTTP_11,
+                Cow::Owned(HeaderMap::new()),
+            );
+            let mut body = state.as_async_read(&mut io);
+            body.reject(response).await.unwrap();
+            assert!(body.state().is_rejected());
+            assert_eq!(
+                io.output,
+                b"HTTP/1.1 100 Continue\r\n\r\n\
+HTTP/1.1 413 Payload Too Large\r\nconnection: close\r\n\r\n"
+            );
+        })
+    }
+
+    #[test]
+    fn unsupported_expectation() {
+        block_on(async {
+            let (head, mut io) = decode(
+                b"PUT / HTTP/1.1\r\ncontent-length: 5\r\nexpect: something\r\n\r\n",
+                b"hello",
+            )
+            .await;
+            let body = BodyDecodeWithContinue::from_head(&head, &mut io).unwrap();
+            assert_eq!(body.state().expectation(), Expectation::Unsupported);
+            assert_eq!(
+                body.state().validate(1024),
+                Err(StatusCode::EXPECTATION_FAILED)
+            );
+            assert!(!body.state().is_continue_pending());
+        })
+    }
 }
@@ -0,0 +1,105 @@
+use crate::{BodyEncodeState, RequestHead, ResponseHead};
+use anyhow::bail;
+use futures::prelude::*;
+use http::header::{CONTENT_LENGTH, TRANSFER_ENCODING};
+
+/// Answers a `CONNECT` request with `response` and returns the transport.
+///
+/// For a `2xx` status the transport becomes a tunnel to the requested authority, see
+/// [`copy_bidirectional`](crate::copy_bidirectional), and any framing headers are removed from
+/// `response`. Other statuses are sent with an empty body and the transport remains an HTTP
+/// connection.
+pub async fn respond_connect<IO: AsyncWrite + Unpin>(
+    mut io: IO,
+    request: &RequestHead<'_>,
+    mut response: ResponseHead<'_>,
+) -> anyhow::Result<IO> {
+    if request.connect_authority().is_none() {
+        bail!("not a CONNECT request in authority-form")
+    }
+    match response.status().is_success() {
+        true => {
+            let headers = response.headers_mut();
+            headers.remove(CONTENT_LENGTH);
+            headers.remove(TRANSFER_ENCODING);
+        }
+        false => {
+            BodyEncodeState::for_response(&mut response, Some(0), request.version());
+        }
+    }
+    response.encode(&mut io).await?;
+    io.flush().await?;
+    Ok(io)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::internal::io_future::IoFutureWithOutputState;
+    use crate::internal::mock_io::MockIo;
+    use crate::{copy_bidirectional, respond_connect, RequestHead, ResponseHead};
+    use futures::executor::block_on;
+    use http::{HeaderMap, StatusCode, Version};
+    use std::borrow::Cow;
+
+    fn response(status: StatusCode) -> ResponseHead<'static> {
+        ResponseHead::new(status, Version::HTTP_11, Cow::Owned(HeaderMap::new()))
+    }
+
+    #[test]
+    fn tunnel() {
+        block_on(async {
+            let mut client = MockIo::new(
+                b"CONNECT example.com:443 HTTP/1.1\r\nhost: example.com:443\r\n\r\nclient hello",
+            );
+            let (_, head) = RequestHead::decode_state()
+                .into_future(&mut client)
+                .await
+                .unwrap();
+            assert_eq!(head.connect_authority().unwrap(), "example.com:443");
+            let mut client = respond_connect(&mut client, &head, response(StatusCode::OK))
+                .await
+                .unwrap();
+            let mut upstream = MockIo::new(b"server hello!");
+            let copied = copy_bidirectional(&mut client, &mut upstream)
+                .await
+                .unwrap();
+            assert_eq!(copied, (12, 13));
+            assert_eq!(upstream.output, b"client hello");
+            assert!(upstream.closed && client.closed);
+            assert_eq!(
+                String::from_utf8(client.output.clone()).unwrap(),
+                "HTTP/1.1 200 OK\r\n\r\nserver hello!"
+            );
+        })
+    }
+
+    #[test]
+    fn refused() {
+        block_on(async {
+            let mut client = MockIo::new(b"GET / HTTP/1.1\r\n\r\n");
+            let (_, head) = RequestHead::decode_state()
+                .into_future(&mut client)
+                .await
+                .unwrap();
+            assert!(head.connect_authority().is_none());
+            assert!(
+                respond_connect(&mut client, &head, response(StatusCode::OK))
+                    .await
+                    .is_err()
+            );
+
+            let mut client = MockIo::new(b"CONNECT example.com:443 HTTP/1.1\r\n\r\n");
+            let (_, head) = RequestHead::decode_state()
+                .into_future(&mut client)
+                .await
+                .unwrap();
+            respond_connect(&mut client, &head, response(StatusCode::BAD_GATEWAY))
+                .await
+                .unwrap();
+            assert_eq!(
+                String::from_utf8(client.output).unwrap(),
+                "HTTP/1.1 502 Bad Gateway\r\ncontent-length: 0\r\n\r\n"
+            );
+        })
+    }
+}
@@ -0,0 +1,341 @@
+use crate::internal::io_future::IoFutureWithOutputState;
+use crate::message::write_complete_response;
+use crate::{
+    switch_protocols, BodyDecodeWithContinue, BodyDecodeWithContinueState, Drained, Limits,
+    RequestHead, ResponseHead,
+};
+use anyhow::bail;
+use futures::prelude::*;
+use http::{HeaderMap, Method, Request, Response, StatusCode, Version};
+use std::io;
+
+/// Settings for a persistent server connection.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct ConnectionConfig {
+    /// Limits for request heads, and for request bodies read by [`serve`].
+    pub limits: Limits,
+    /// Maximum number of requests handled on one connection.
+    pub max_requests: Option<usize>,
+    /// Maximum number of unread request body bytes discarded to keep the connection alive.
+    pub drain_limit: u64,
+}
+
+impl Default for ConnectionConfig {
+    fn default() -> Self {
+        Self {
+            limits: Limits::default(),
+            max_requests: Some(100),
+            drain_limit: 64 * 1024,
+        }
+    }
+}
+
+/// Server side of a persistent connection, handling one request after another.
+///
+/// Each request head is obtained with [`next_request`](Self::next_request), its body can be
+/// read through [`body`](Self::body), and [`respond`](Self::respond) sends the response and
+/// discards whatever the handler didn't read from the request body.
+/// Wrapping the transport in a [`ReadBuffer`](crate::ReadBuffer) avoids many small reads.
+pub struct ServerConnection<IO: AsyncRead + AsyncWrite + Unpin> {
+    io: IO,
+    config: ConnectionConfig,
+    served: usize,
+    body: BodyDecodeWithContinueState,
+    request_method: Method,
+    request_version: Version,
+    persistent: bool,
+    responded: bool,
+}
+
+impl<IO: AsyncRead + AsyncWrite + Unpin> ServerConnection<IO> {
+    pub fn new(io: IO, config: ConnectionConfig) -> Self {
+        Self {
+            io,
+            config,
+            served: 0,
+            body: BodyDecodeWithContinueState::new(Version::HTTP_11, Some(0), false),
+            request_method: Method::GET,
+            request_version: Version::HTTP_11,
+            persistent: true,
+            responded: true,
+        }
+    }
+    pub fn into_inner(self) -> IO {
+        self.io
+    }
+    /// Decodes the next request head. Returns `None` once the connection is done, either because
+    /// the previous exchange ended persistence, the request limit was reached, or the client
+    /// closed the connection between requests. A connection closed within a head is an error.
+    pub async fn next_request(&mut self) -> anyhow::Result<Option<RequestHead<'static>>> {
+        if !self.responded {
+            bail!("the previous request has not been responded to")
+        }
+        if !self.persistent || Some(self.served) == self.config.max_requests {
+            return Ok(None);
+        }
+        let limits = &self.config.limits;
+        let mut state = RequestHead::decode_state_with_limits(limits.max_head, limits.max_headers);
+        let head = match future::poll_fn(|cx| state.poll(cx, &mut self.io)).await {
+            Ok(head) => head,
+            Err(err)
+                if err.kind() == io::ErrorKind::UnexpectedEof && state.buffered().is_empty() =>
+            {
+                return Ok(None)
+            }
+            Err(err) => return Err(err.into()),
+        };
+        self.body = BodyDecodeWithContinueState::from_head(&head)?;
+        self.request_method = head.method();
+        self.request_version = head.version();
+        self.persistent = head.is_persistent();
+        self.served += 1;
+        self.responded = false;
+        Ok(Some(head))
+    }
+    /// Body of the current request.
+    pub fn body(&mut self) -> BodyDecodeWithContinue<&mut BodyDecodeWithContinueState, &mut IO> {
+        self.body.as_async_read(&mut self.io)
+    }
+    /// Sends the response to the current request, discards the unread request body and decides
+    /// whether the connection persists. The connection is closed if it doesn't.
+    pub async fn respond<B: AsRef<[u8]>>(&mut self, response: &Response<B>) -> anyhow::Result<()> {
+        if self.responded {
+            bail!("there is no request to respond to")
+        }
+        self.responded = true;
+        let persistent =
+            self.persistent && self.config.max_requests != Some(self.served) && self.drainable();
+        self.persistent = write_response(
+            &mut self.io,
+            response,
+            &self.request_method,
+            self.request_version,
+            persistent,
+        )
+        .await?;
+
+        if self.persistent {
+            let limit = self.config.drain_limit;
+            let drained = self.body().drain(limit).await?;
+            self.persistent = drained == Drained::Complete;
+        }
+        if !self.persistent {
+            self.io.close().await?;
+        }
+        Ok(())
+    }
+    /// Switches the connection to `protocol` in response to the current request, see
+    /// [`switch_protocols`]. The request body is discarded first, within the drain limit.
+    pub async fn upgrade(
+        mut self,
+        request: &RequestHead<'_>,
+        protocol: &str,
+        headers: HeaderMap,
+    ) -> anyhow::Result<IO> {
+        if self.responded {
+            bail!("there is no request to respond to")
+        }
+        let limit = self.config.drain_limit;
+        if self.body().drain(limit).await? != Drained::Complete {
+            bail!("request body exceeds the drain limit")
+        }
+        switch_protocols(self.io, request, protocol, headers).await
+    }
+    /// Whether the unread request body can be drained within the limit.
+    fn drainable(&self) -> bool {
+        let body = self.body.body();
+        if body.is_done() {
+            return true;
+        }
+        !self.body.is_continue_pending()
+            && !self.body.is_rejected()
+            && matches!(body.remaining(), Some(remaining) if remaining <= self.config.drain_limit)
+    }
+}
+
+/// Handles the requests on a connection until it is closed, reading each request body into
+/// memory before passing the request to `handler`.
+///
+/// Requests with bodies exceeding the configured limit are answered with
+/// `413 Payload Too Large` and requests with unsupported expectations with
+/// `417 Expectation Failed`, without calling `handler`. If `handler` fails, a
+/// `500 Internal Server Error` response is sent and the error is returned.
+pub async fn serve<IO, H, F, B>(
+    io: IO,
+    config: ConnectionConfig,
+    mut handler: H,
+) -> anyhow::Result<()>
+where
+    IO: AsyncRead + AsyncWrite + Unpin,
+    H: FnMut(Request<Vec<u8>>) -> F,
+    F: Future<Output = anyhow::Result<Response<B>>>,
+    B: AsRef<[u8]>,
+{
+    let max_body = config.limits.max_body;
+    let mut connection = ServerConnection::new(io, config);
+    while let Some(head) = connection.next_request().await? {
+        if let Err(status) = connection.body.validate(max_body) {
+            future::poll_fn(|cx| connection.body.poll_reject(cx, &mut connection.io)).await?;
+            connection.respond(&status_response(status)).await?;
+            continue;
+        }
+        let mut body = Vec::new();
+        connection
+            .body()
+            .take(max_body.saturating_add(1))
+            .read_to_end(&mut body)
+            .await?;
+        if body.len() as u64 > max_body {
+            connection.persistent = false;
+            connection
+                .respond(&status_response(StatusCode::PAYLOAD_TOO_LARGE))
+                .await?;
+            continue;
+        }
+        match handler(Request::from_parts(head.into(), body)).await {
+            Ok(response) => connection.respond(&response).await?,
+            Err(err) => {
+                connection.persistent = false;
+                connection
+                    .respond(&status_response(StatusCode::INTERNAL_SERVER_ERROR))
+                    .await?;
+                return Err(err);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Writes the response to a `method` request with `Connection` options matching `persistent`,
+/// unless the response itself asks to close. Returns whether the connection persists.
+pub(crate) async fn write_response<IO: AsyncWrite + Unpin, B: AsRef<[u8]>>(
+    io: IO,
+    response: &Response<B>,
+    method: &Method,
+    peer: Version,
+    persistent: bool,
+) -> anyhow::Result<bool> {
+    let mut head = ResponseHead::ref_response(response);
+    let persistent = persistent && !head.has_connection_token("close");
+    head.set_persistent(persistent, peer);
+    write_complete_response(io, &mut head, response.body().as_ref(), method, peer).await?;
+    Ok(persistent)
+}
+
+pub(crate) fn status_response(status: StatusCode) -> Response<&'static [u8]> {
+    let mut response = Response::new(&b""[..]);
+    *response.status_mut() = status;
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::internal::mock_io::MockIo;
+    use crate::{serve, ConnectionConfig};
+    use futures::executor::block_on;
+    use http::{Request, Response, StatusCode};
+
+    async fn echo(request: Request<Vec<u8>>) -> anyhow::Result<Response<Vec<u8>>> {
+        Ok(Response::new(request.into_body()))
+    }
+
+    async fn hello(request: Request<Vec<u8>>) -> anyhow::Result<Response<&'static str>> {
+        let mut response = Response::new("hello");
+        if request.uri().path() == "/empty" {
+            *response.status_mut() = StatusCode::NO_CONTENT;
+        }
+        Ok(response)
+    }
+
+    #[test]
+    fn keep_alive() {
+        block_on(async {
+            let mut io = MockIo::new(
+                b"POST / HTTP/1.1\r\ncontent-length: 5\r\n\r\nhello\
+                GET / HTTP/1.1\r\n\r\n\
+                POST / HTTP/1.0\r\nconnection: keep-alive\r\ncontent-length: 3\r\n\r\nfoo\
+                POST / HTTP/1.1\r\nconnection: close\r\ncontent-length: 3\r\n\r\nbar\
+                GET / HTTP/1.1\r\n\r\n",
+            );
+            serve(&mut io, ConnectionConfig::default(), echo)
+                .await
+                .unwrap();
+            assert_eq!(
+                String::from_utf8(io.output.clone()).unwrap(),
+                "HTTP/1.1 200 OK\r\ncontent-length: 5\r\n\r\nhello\
+                HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n\
+                HTTP/1.1 200 OK\r\nconnection: keep-alive\r\ncontent-length: 3\r\n\r\nfoo\
+                HTTP/1.1 200 OK\r\nconnection: close\r\ncontent-length: 3\r\n\r\nbar"
+            );
+            assert!(io.closed);
+            assert_eq!(io.remaining_input(), b"GET / HTTP/1.1\r\n\r\n");
+        })
+    }
+
+    #[test]
+    fn request_limits() {
+        block_on(async {
+            let mut io = MockIo::new(
+                b"POST / HTTP/1.1\r\ncontent-length: 5\r\n\r\nhello\
+                POST / HTTP/1.1\r\ncontent-length: 3\r\n\r\nfoo",
+            );
+            let config = ConnectionConfig {
+                max_requests: Some(1),
+                ..ConnectionConfig::default()
+            };
+            serve(&mut io, config, echo).await.unwrap();
+            assert_eq!(
+                String::from_utf8(io.output.clone()).unwrap(),
+                "HTTP/1.1 200 OK\r\nconnection: close\r\ncontent-length: 5\r\n\r\nhello"
+            );
+
+            let mut io = MockIo::new(b"POST / HTTP/1.1\r\ncontent-length: 5\r\n\r\nhello");
+            let mut config = ConnectionConfig::default();
+            config.limits.max_body = 4;
+            serve(&mut io, config, echo).await.unwrap();
+            assert_eq!(
+                String::from_utf8(io.output.clone()).unwrap(),
+                "HTTP/1.1 413 Payload Too Large\r\nconnection: close\r\ncontent-length: 0\r\n\r\n"
+            );
+        })
+    }
+
+    #[test]
+    fn responses_without_body() {
+        block_on(async {
+            let mut io = MockIo::new(
+                b"HEAD / HTTP/1.1\r\n\r\n\
+                GET /empty HTTP/1.1\r\n\r\n\
+                GET / HTTP/1.1\r\nconnection: close\r\n\r\n",
+            );
+            serve(&mut io, ConnectionConfig::default(), hello)
+                .await
+                .unwrap();
+            assert_eq!(
+                String::from_utf8(io.output.clone()).unwrap(),
+                "HTTP/1.1 200 OK\r\ncontent-length: 5\r\n\r\n\
+                HTTP/1.1 204 No Content\r\n\r\n\
+                HTTP/1.1 200 OK\r\nconnection: close\r\ncontent-length: 5\r\n\r\nhello"
+            );
+        })
+    }
+
+    #[test]
+    fn truncated_head() {
+        block_on(async {
+            let mut io = MockIo::new(b"GET / HTTP/1.1\r\n\r\nGET / HTTP/1.1\r\nhost");
+            let result = serve(&mut io, ConnectionConfig::default(), hello).await;
+            assert!(result.is_err());
+            assert_eq!(
+                String::from_utf8(io.output.clone()).unwrap(),
+                "HTTP/1.1 200 OK\r\ncontent-length: 5\r\n\r\nhello"
+            );
+
+            let mut io = MockIo::new(b"");
+            serve(&mut io, ConnectionConfig::default(), hello)
+                .await
+                .unwrap();
+            assert!(io.output.is_empty());
+        })
+    }
+}
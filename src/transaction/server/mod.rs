@@ -1,3 +1,13 @@
 mod body_decode_with_continue;
+mod connect;
+mod connection;
+mod pipelined;
+mod response_writer;
+mod upgrade;
 
 pub use body_decode_with_continue::*;
+pub use connect::*;
+pub use connection::*;
+pub use pipelined::*;
+pub use response_writer::*;
+pub use upgrade::*;
@@ -0,0 +1,324 @@
+use crate::internal::io_future::IoFutureWithOutputState;
+use crate::transaction::server::connection::{status_response, write_response};
+use crate::{BodyDecodeState, BodyDecodeWithContinueState, ConnectionConfig, ReadBuffer};
+use crate::{RequestHead, ResponseHead};
+use futures::channel::mpsc;
+use futures::future::{select, Either};
+use futures::prelude::*;
+use http::{HeaderMap, Method, Request, Response, StatusCode, Version};
+use std::borrow::Cow;
+use std::io;
+
+/// Like [`serve`](crate::serve), but reads and handles up to `concurrency` pipelined requests
+/// while earlier responses are pending. Responses are still written in request order.
+///
+/// Reads are buffered with a [`ReadBuffer`], so requests arriving together are not lost.
+pub async fn serve_pipelined<IO, H, F, B>(
+    io: IO,
+    config: ConnectionConfig,
+    concurrency: usize,
+    handler: H,
+) -> anyhow::Result<()>
+where
+    IO: AsyncRead + AsyncWrite + Unpin,
+    H: FnMut(Request<Vec<u8>>) -> F,
+    F: Future<Output = anyhow::Result<Response<B>>>,
+    B: AsRef<[u8]>,
+{
+    let concurrency = concurrency.max(1);
+    let (reader, writer) = io.split();
+    let (jobs, queue) = mpsc::channel(concurrency);
+    let reader = read_requests(ReadBuffer::new(reader), config, jobs, handler);
+    let writer = write_responses(writer, queue, concurrency);
+    futures::pin_mut!(reader, writer);
+    match select(writer, reader).await {
+        Either::Left((result, _)) => result,
+        Either::Right((Ok(()), writer)) => writer.await,
+        Either::Right((Err(err), writer)) => {
+            writer.await.ok();
+            Err(err)
+        }
+    }
+}
+
+struct Exchange {
+    method: Method,
+    version: Version,
+    persistent: bool,
+}
+
+enum Job<F> {
+    Continue(Version),
+    Status(Exchange, StatusCode),
+    Handle(Exchange, F),
+}
+
+enum Outcome<B> {
+    Continue(Version),
+    Status(Exchange, StatusCode),
+    Response(Exchange, anyhow::Result<Response<B>>),
+}
+
+async fn read_requests<IO, H, F>(
+    mut io: ReadBuffer<IO>,
+    config: ConnectionConfig,
+    mut jobs: mpsc::Sender<Job<F>>,
+    mut handler: H,
+) -> anyhow::Result<()>
+where
+    IO: AsyncRead + Unpin,
+    H: FnMut(Request<Vec<u8>>) -> F,
+{
+    let limits = config.limits;
+    let mut served = 0;
+    while Some(served) != config.max_requests {
+        let mut state = RequestHead::decode_state_with_limits(limits.max_head, limits.max_headers);
+        let head = match future::poll_fn(|cx| state.poll(cx, &mut io)).await {
+            Ok(head) => head,
+            Err(err)
+                if err.kind() == io::ErrorKind::UnexpectedEof && state.buffered().is_empty() =>
+            {
+                break
+            }
+            Err(err) => return Err(err.into()),
+        };
+        served += 1;
+        let mut exchange = Exchange {
+            method: head.method(),
+            version: head.version(),
+            persistent: head.is_persistent() && Some(served) != config.max_requests,
+        };
+        let expectation = BodyDecodeWithContinueState::from_head(&head)?;
+        if let Err(status) = expectation.validate(limits.max_body) {
+            exchange.persistent = false;
+            jobs.send(Job::Status(exchange, status)).await.ok();
+            break;
+        }
+        if expectation.is_continue_pending()
+            && jobs.send(Job::Continue(head.version())).await.is_err()
+        {
+            break;
+        }
+        let mut body = Vec::new();
+        BodyDecodeState::from_headers(head.headers())?
+            .into_async_read(&mut io)
+            .take(limits.max_body.saturating_add(1))
+            .read_to_end(&mut body)
+            .await?;
+        if body.len() as u64 > limits.max_body {
+            exchange.persistent = false;
+            jobs.send(Job::Status(exchange, StatusCode::PAYLOAD_TOO_LARGE))
+                .await
+                .ok();
+            break;
+        }
+        let persistent = exchange.persistent;
+        let job = Job::Handle(exchange, handler(Request::from_parts(head.into(), body)));
+        if jobs.send(job).await.is_err() || !persistent {
+            break;
+        }
+    }
+    Ok(())
+}
+
+async fn write_responses<IO, F, B>(
+    mut io: IO,
+    queue: mpsc::Receiver<Job<F>>,
+    concurrency: usize,
+) -> anyhow::Result<()>
+where
+    IO: AsyncWrite + Unpin,
+    F: Future<Output = anyhow::Result<Response<B>>>,
+    B: AsRef<[u8]>,
+{
+    let outcomes = queue
+        .map(|job| async move {
+            match job {
+                Job::Continue(version) => Outcome::Continue(version),
+                Job::Status(exchange, status) => Outcome::Status(exchange, status),
+                Job::Handle(exchange, future) => Outcome::Response(exchange, future.await),
+            }
+        })
+        .buffered(concurrency);
+    futures::pin_mut!(outcomes);
+    while let Some(outcome) = outcomes.next().await {
+        let persistent = match outcome {
+            Outcome::Continue(version) => {
+                ResponseHead::new(StatusCode::CONTINUE, version, Cow::Owned(HeaderMap::new()))
+                    .encode(&mut io)
+                    .await?;
+                io.flush().await?;
+                true
+            }
+            Outcome::Status(exchange, status) => {
+                let response = status_response(status);
+                write_response(
+                    &mut io,
+                    &response,
+                    &exchange.method,
+                    exchange.version,
+                    false,
+                )
+                .await?
+            }
+            Outcome::Response(exchange, Ok(response)) => {
+                write_response(
+                    &mut io,
+                    &response,
+                    &exchange.method,
+                    exchange.version,
+                    exchange.persistent,
+                )
+                .await?
+            }
+            Outcome::Response(exchange, Err(err)) => {
+                let response = status_response(StatusCode::INTERNAL_SERVER_ERROR);
+                write_response(
+                    &mut io,
+                    &response,
+                    &exchange.method,
+                    exchange.version,
+                    false,
+                )
+                .await?;
+                io.close().await?;
+                return Err(err);
+            }
+        };
+        if !persistent {
+            break;
+        }
+    }
+    io.close().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::internal::mock_io::MockIo;
+    use crate::{serve_pipelined, ConnectionConfig};
+    use futures::channel::oneshot;
+    use futures::executor::block_on;
+    use futures::prelude::*;
+    use futures::task::noop_waker_ref;
+    use http::{Request, Response};
+    use std::pin::pin;
+    use std::task::{Context, Poll};
+
+    /// Serves requests where `/slow` only completes after `/fast` started, returning the output
+    /// if the connection was served within a bounded number of polls.
+    fn serve_slow_fast(concurrency: usize) -> Option<String> {
+        let mut io = MockIo::new(
+            b"GET /slow HTTP/1.1\r\n\r\n\
+            POST /fast HTTP/1.1\r\ncontent-length: 3\r\n\r\nfoo\
+            POST /last HTTP/1.1\r\nexpect: 100-continue\r\nconnection: close\r\n\
+            content-length: 3\r\n\r\nbar",
+        );
+        let (tx, rx) = oneshot::channel::<()>();
+        let (mut tx, mut rx) = (Some(tx), Some(rx));
+        let handler = move |request: Request<Vec<u8>>| {
+            let path = request.uri().path().to_string();
+            let (tx, rx) = match path.as_str() {
+                "/slow" => (None, rx.take()),
+                "/fast" => (tx.take(), None),
+                _ => (None, None),
+            };
+            async move {
+                if let Some(tx) = tx {
+                    tx.send(()).unwrap();
+                }
+                if let Some(rx) = rx {
+                    rx.await.unwrap();
+                }
+                Ok(Response::new(path))
+            }
+        };
+        let served = {
+            let mut serve = pin!(serve_pipelined(
+                &mut io,
+                ConnectionConfig::default(),
+                concurrency,
+                handler
+            ));
+            let mut cx = Context::from_waker(noop_waker_ref());
+            (0..100).any(|_| match serve.as_mut().poll(&mut cx) {
+                Poll::Ready(result) => {
+                    result.unwrap();
+                    true
+                }
+                Poll::Pending => false,
+            })
+        };
+        match served {
+            true => {
+                assert!(io.closed);
+                Some(String::from_utf8(io.output).unwrap())
+            }
+            false => None,
+        }
+    }
+
+    #[test]
+    fn concurrent_in_order() {
+        assert_eq!(
+            serve_slow_fast(2).unwrap(),
+            "HTTP/1.1 200 OK\r\ncontent-length: 5\r\n\r\n/slow\
+            HTTP/1.1 200 OK\r\ncontent-length: 5\r\n\r\n/fast\
+            HTTP/1.1 100 Continue\r\n\r\n\
+            HTTP/1.1 200 OK\r\nconnection: close\r\ncontent-length: 5\r\n\r\n/last"
+        );
+    }
+
+    #[test]
+    fn head_request() {
+        let mut io = MockIo::new(
+            b"HEAD / HTTP/1.1\r\n\r\n\
+            GET / HTTP/1.1\r\nconnection: close\r\n\r\n",
+        );
+        let handler = |_| async { Ok(Response::new("hello")) };
+        block_on(serve_pipelined(
+            &mut io,
+            ConnectionConfig::default(),
+            2,
+            handler,
+        ))
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(io.output).unwrap(),
+            "HTTP/1.1 200 OK\r\ncontent-length: 5\r\n\r\n\
+            HTTP/1.1 200 OK\r\nconnection: close\r\ncontent-length: 5\r\n\r\nhello"
+        );
+    }
+
+    #[test]
+    fn unlimited_body() {
+        let mut io =
+            MockIo::new(b"POST / HTTP/1.1\r\nconnection: close\r\ncontent-length: 3\r\n\r\nfoo");
+        let mut config = ConnectionConfig::default();
+        config.limits.max_body = u64::MAX;
+        let handler = |request: Request<Vec<u8>>| async { Ok(Response::new(request.into_body())) };
+        block_on(serve_pipelined(&mut io, config, 2, handler)).unwrap();
+        assert_eq!(
+            String::from_utf8(io.output).unwrap(),
+            "HTTP/1.1 200 OK\r\nconnection: close\r\ncontent-length: 3\r\n\r\nfoo"
+        );
+    }
+
+    #[test]
+    fn truncated_head() {
+        let mut io = MockIo::new(b"GET / HTTP/1.1\r\nhost");
+        let handler = |_| async { Ok(Response::new("hello")) };
+        let result = block_on(serve_pipelined(
+            &mut io,
+            ConnectionConfig::default(),
+            2,
+            handler,
+        ));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn sequential_without_concurrency() {
+        assert!(serve_slow_fast(1).is_none());
+    }
+}
@@ -0,0 +1,166 @@
+use crate::internal::io_future::IoFutureState;
+use crate::{BodyEncode, BodyEncodeState, RequestHead, ResponseHead};
+use futures::prelude::*;
+use http::header::{CONTENT_LENGTH, TRANSFER_ENCODING};
+use http::{HeaderValue, Method, StatusCode, Version};
+use std::io;
+
+/// Writes the response to a request, optionally preceded by informational (1xx) responses.
+/// Sending the final head consumes the writer, so informational responses can't follow it.
+pub struct ResponseWriter<IO: AsyncWrite + Unpin> {
+    io: IO,
+    request_method: Method,
+    peer: Version,
+}
+
+impl<IO: AsyncWrite + Unpin> ResponseWriter<IO> {
+    pub fn new(io: IO, request: &RequestHead) -> Self {
+        Self::with_peer(io, &request.method(), request.version())
+    }
+    pub fn with_peer(io: IO, request_method: &Method, peer: Version) -> Self {
+        Self {
+            io,
+            request_method: request_method.clone(),
+            peer,
+        }
+    }
+    pub fn into_inner(self) -> IO {
+        self.io
+    }
+    /// Writes and flushes an informational response head, e.g. `103 Early Hints`.
+    /// HTTP/1.0 clients don't understand informational responses, so sending them fails.
+    pub async fn send_interim(&mut self, head: &ResponseHead<'_>) -> io::Result<()> {
+        if !head.status().is_informational() || head.status() == StatusCode::SWITCHING_PROTOCOLS {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "not an informational status",
+            ));
+        }
+        if self.peer < Version::HTTP_11 {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "informational responses can't be sent to HTTP/1.0 clients",
+            ));
+        }
+        head.encode_state().into_future(&mut self.io).await?;
+        self.io.flush().await
+    }
+    /// Writes the final response head with framing chosen by
+    /// [`BodyEncodeState::from_response_head`] and returns the matching body encoder. A known
+    /// `length` replaces the framing headers of `head` with a matching `Content-Length`.
+    pub async fn send_final(
+        mut self,
+        mut head: ResponseHead<'_>,
+        length: Option<u64>,
+    ) -> io::Result<BodyEncode<IO>> {
+        if let Some(length) = length {
+            let headers = head.headers_mut();
+            headers.remove(TRANSFER_ENCODING);
+            headers.insert(CONTENT_LENGTH, HeaderValue::from(length));
+        }
+        let state = BodyEncodeState::from_response_head(&mut head, &self.request_method, self.peer)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err.to_string()))?;
+        head.encode(&mut self.io).await?;
+        Ok(state.into_async_write(self.io))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::internal::mock_io::MockIo;
+    use crate::{ResponseHead, ResponseWriter};
+    use futures::executor::block_on;
+    use futures::prelude::*;
+    use http::header::LINK;
+    use http::{HeaderMap, Method, StatusCode, Version};
+    use std::borrow::Cow;
+
+    fn head(status: StatusCode) -> ResponseHead<'static> {
+        ResponseHead::new(status, Version::HTTP_11, Cow::Owned(HeaderMap::new()))
+    }
+
+    #[test]
+    fn early_hints() {
+        block_on(async {
+            let mut io = MockIo::new(b"");
+            let mut writer = ResponseWriter::with_peer(&mut io, &Method::GET, Version::HTTP_11);
+            let mut hints = head(StatusCode::EARLY_HINTS);
+            hints
+                .headers_mut()
+                .insert(LINK, "</style.css>; rel=preload".parse().unwrap());
+            writer.send_interim(&hints).await.unwrap();
+            assert!(writer.send_interim(&head(StatusCode::OK)).await.is_err());
+            let mut body = writer
+                .send_final(head(StatusCode::OK), Some(5))
+                .await
+                .unwrap();
+            body.write_all(b"hello").await.unwrap();
+            body.finish().await.unwrap();
+            assert_eq!(
+                String::from_utf8(io.output).unwrap(),
+                "HTTP/1.1 103 Early Hints\r\nlink: </style.css>; rel=preload\r\n\r\n\
+                HTTP/1.1 200 OK\r\ncontent-length: 5\r\n\r\nhello"
+            );
+        })
+    }
+
+    #[test]
+    fn http10_peer() {
+        block_on(async {
+            let mut io = MockIo::new(b"");
+            let mut writer = ResponseWriter::with_peer(&mut io, &Method::GET, Version::HTTP_10);
+            assert!(writer
+                .send_interim(&head(StatusCode::EARLY_HINTS))
+                .await
+                .is_err());
+            assert!(io.output.is_empty());
+        })
+    }
+
+    #[test]
+    fn head_response() {
+        block_on(async {
+            let mut io = MockIo::new(b"");
+            let writer = ResponseWriter::with_peer(&mut io, &Method::HEAD, Version::HTTP_11);
+            let mut body = writer
+                .send_final(head(StatusCode::OK), Some(5))
+                .await
+                .unwrap();
+            assert!(body.write_all(b"hello").await.is_err());
+            assert_eq!(
+                String::from_utf8(io.output).unwrap(),
+                "HTTP/1.1 200 OK\r\ncontent-length: 5\r\n\r\n"
+            );
+        })
+    }
+
+    #[test]
+    fn connect_response() {
+        block_on(async {
+            for length in [None, Some(5)] {
+                let mut io = MockIo::new(b"");
+                let writer = ResponseWriter::with_peer(&mut io, &Method::CONNECT, Version::HTTP_11);
+                let mut body = writer
+                    .send_final(head(StatusCode::OK), length)
+                    .await
+                    .unwrap();
+                body.finish().await.unwrap();
+                assert_eq!(
+                    String::from_utf8(io.output).unwrap(),
+                    "HTTP/1.1 200 OK\r\n\r\n"
+                );
+            }
+
+            let mut io = MockIo::new(b"");
+            let writer = ResponseWriter::with_peer(&mut io, &Method::CONNECT, Version::HTTP_11);
+            writer
+                .send_final(head(StatusCode::BAD_GATEWAY), None)
+                .await
+                .unwrap();
+            assert_eq!(
+                String::from_utf8(io.output).unwrap(),
+                "HTTP/1.1 502 Bad Gateway\r\ntransfer-encoding: chunked\r\n\r\n"
+            );
+        })
+    }
+}
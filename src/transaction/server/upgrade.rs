@@ -0,0 +1,87 @@
+use crate::{RequestHead, ResponseHead};
+use anyhow::bail;
+use futures::prelude::*;
+use http::header::{CONNECTION, UPGRADE};
+use http::{HeaderMap, HeaderValue, StatusCode, Version};
+use std::borrow::Cow;
+
+/// Sends `101 Switching Protocols` for one of the protocols offered by `request` and returns the
+/// transport, which then carries `protocol`.
+///
+/// `headers` are added to the response. If reads on `io` are buffered, e.g. by a
+/// [`ReadBuffer`](crate::ReadBuffer), bytes the client sent after the request stay buffered.
+pub async fn switch_protocols<IO: AsyncWrite + Unpin>(
+    mut io: IO,
+    request: &RequestHead<'_>,
+    protocol: &str,
+    mut headers: HeaderMap,
+) -> anyhow::Result<IO> {
+    if !request
+        .upgrade_protocols()
+        .any(|offered| offered.eq_ignore_ascii_case(protocol))
+    {
+        bail!("the request does not offer an upgrade to {:?}", protocol)
+    }
+    headers.insert(CONNECTION, HeaderValue::from_static("upgrade"));
+    headers.insert(UPGRADE, HeaderValue::from_str(protocol)?);
+    ResponseHead::new(
+        StatusCode::SWITCHING_PROTOCOLS,
+        Version::HTTP_11,
+        Cow::Owned(headers),
+    )
+    .encode(&mut io)
+    .await?;
+    io.flush().await?;
+    Ok(io)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::internal::mock_io::MockIo;
+    use crate::{switch_protocols, ConnectionConfig, ReadBuffer, RequestHead, ServerConnection};
+    use futures::executor::block_on;
+    use futures::prelude::*;
+    use http::{HeaderMap, HeaderValue, Request};
+
+    #[test]
+    fn upgrade() {
+        block_on(async {
+            let io = MockIo::new(
+                b"GET /chat HTTP/1.1\r\nconnection: keep-alive, Upgrade\r\nupgrade: foo/2, bar\r\n\r\nraw bytes",
+            );
+            let mut connection =
+                ServerConnection::new(ReadBuffer::new(io), ConnectionConfig::default());
+            let head = connection.next_request().await.unwrap().unwrap();
+            assert_eq!(
+                head.upgrade_protocols().collect::<Vec<_>>(),
+                ["foo/2", "bar"]
+            );
+            let mut headers = HeaderMap::new();
+            headers.insert("x-test", HeaderValue::from_static("1"));
+            let mut io = connection.upgrade(&head, "bar", headers).await.unwrap();
+            let mut raw = String::new();
+            io.read_to_string(&mut raw).await.unwrap();
+            assert_eq!(raw, "raw bytes");
+            assert_eq!(
+                String::from_utf8(io.into_parts().0.output).unwrap(),
+                "HTTP/1.1 101 Switching Protocols\r\nx-test: 1\r\nconnection: upgrade\r\nupgrade: bar\r\n\r\n"
+            );
+        })
+    }
+
+    #[test]
+    fn not_offered() {
+        block_on(async {
+            let request = Request::get("/")
+                .version(http::Version::HTTP_10)
+                .header("connection", "upgrade")
+                .header("upgrade", "foo")
+                .body(())
+                .unwrap();
+            let head = RequestHead::ref_request(&request);
+            assert_eq!(head.upgrade_protocols().count(), 0);
+            let result = switch_protocols(MockIo::new(b""), &head, "foo", HeaderMap::new()).await;
+            assert!(result.is_err());
+        })
+    }
+}
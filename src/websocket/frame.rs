@@ -0,0 +1,301 @@
+use crate::internal::buffer_write::{BufferWrite, BufferWriteState};
+use crate::internal::io_future::{IoFutureState, IoFutureWithOutput, IoFutureWithOutputState};
+use crate::websocket::ProtocolError;
+use futures::prelude::*;
+use std::io;
+use std::io::ErrorKind::InvalidData;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// Side of the connection, which determines the masking of frames.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Role {
+    Client,
+    Server,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Opcode {
+    Continuation,
+    Text,
+    Binary,
+    Close,
+    Ping,
+    Pong,
+}
+
+impl Opcode {
+    fn from_u8(opcode: u8) -> io::Result<Self> {
+        Ok(match opcode {
+            0x0 => Self::Continuation,
+            0x1 => Self::Text,
+            0x2 => Self::Binary,
+            0x8 => Self::Close,
+            0x9 => Self::Ping,
+            0xA => Self::Pong,
+            _ => return Err(io::Error::new(InvalidData, "unknown websocket opcode")),
+        })
+    }
+    fn to_u8(self) -> u8 {
+        match self {
+            Self::Continuation => 0x0,
+            Self::Text => 0x1,
+            Self::Binary => 0x2,
+            Self::Close => 0x8,
+            Self::Ping => 0x9,
+            Self::Pong => 0xA,
+        }
+    }
+    pub fn is_control(self) -> bool {
+        matches!(self, Self::Close | Self::Ping | Self::Pong)
+    }
+}
+
+/// A websocket frame with unmasked payload.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Frame {
+    pub fin: bool,
+    pub opcode: Opcode,
+    pub payload: Vec<u8>,
+}
+
+impl Frame {
+    pub fn new(fin: bool, opcode: Opcode, payload: Vec<u8>) -> Self {
+        Self {
+            fin,
+            opcode,
+            payload,
+        }
+    }
+    /// Serializes the frame, masking the payload if `mask` is given, as required for clients.
+    pub fn to_vec(&self, mask: Option<[u8; 4]>) -> io::Result<Vec<u8>> {
+        let len = self.payload.len();
+        if self.opcode.is_control() && (len > 125 || !self.fin) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "control frames must be final and have at most 125 bytes of payload",
+            ));
+        }
+        let mut buffer = Vec::with_capacity(len + 14);
+        buffer.push((self.fin as u8) << 7 | self.opcode.to_u8());
+        let mask_bit = (mask.is_some() as u8) << 7;
+        match len {
+            0..=125 => buffer.push(mask_bit | len as u8),
+            126..=0xFFFF => {
+                buffer.push(mask_bit | 126);
+                buffer.extend_from_slice(&(len as u16).to_be_bytes());
+            }
+            _ => {
+                buffer.push(mask_bit | 127);
+                buffer.extend_from_slice(&(len as u64).to_be_bytes());
+            }
+        }
+        let start = buffer.len();
+        if let Some(mask) = mask {
+            buffer.extend_from_slice(&mask);
+        }
+        buffer.extend_from_slice(&self.payload);
+        if let Some(mask) = mask {
+            apply_mask(&mut buffer[start + 4..], mask);
+        }
+        Ok(buffer)
+    }
+    /// Writes the frame, masking it with a random key for [`Role::Client`].
+    pub fn encode<IO: AsyncWrite + Unpin>(&self, io: IO, role: Role) -> BufferWrite<IO> {
+        self.encode_state(role).into_future(io)
+    }
+    pub fn encode_state(&self, role: Role) -> BufferWriteState {
+        BufferWriteState::new(self.to_vec(mask_for(role)))
+    }
+    /// Reads a frame sent to the endpoint with `role`, rejecting payloads larger than
+    /// `max_payload`. Servers require masked frames, clients unmasked ones.
+    pub fn decode<IO: AsyncRead + Unpin>(io: IO, role: Role, max_payload: u64) -> FrameDecode<IO> {
+        Self::decode_state(role, max_payload).into_future(io)
+    }
+    pub fn decode_state(role: Role, max_payload: u64) -> FrameDecodeState {
+        FrameDecodeState::new(role, max_payload)
+    }
+}
+
+pub struct FrameDecodeState {
+    role: Role,
+    max_payload: u64,
+    head: [u8; 14],
+    head_len: usize,
+    frame: Option<(Frame, Option<[u8; 4]>)>,
+    completion: usize,
+}
+
+impl FrameDecodeState {
+    pub fn new(role: Role, max_payload: u64) -> Self {
+        Self {
+            role,
+            max_payload,
+            head: [0u8; 14],
+            head_len: 0,
+            frame: None,
+            completion: 0,
+        }
+    }
+    /// Length of the frame head, as far as it is known from the bytes read so far.
+    fn expected_head_len(&self) -> usize {
+        if self.head_len < 2 {
+            return 2;
+        }
+        let extended = match self.head[1] & 0x7F {
+            126 => 2,
+            127 => 8,
+            _ => 0,
+        };
+        let mask = match self.head[1] & 0x80 {
+            0 => 0,
+            _ => 4,
+        };
+        2 + extended + mask
+    }
+    fn parse_head(&self) -> io::Result<(Frame, Option<[u8; 4]>)> {
+        let head = &self.head[..self.head_len];
+        if head[0] & 0x70 != 0 {
+            return Err(io::Error::new(InvalidData, "reserved websocket bits set"));
+        }
+        let fin = head[0] & 0x80 != 0;
+        let opcode = Opcode::from_u8(head[0] & 0x0F)?;
+        let (len, rest) = match head[1] & 0x7F {
+            126 => (u16::from_be_bytes([head[2], head[3]]) as u64, &head[4..]),
+            127 => (
+                u64::from_be_bytes(head[2..10].try_into().unwrap()),
+                &head[10..],
+            ),
+            len => (len as u64, &head[2..]),
+        };
+        let mask = match rest.len() {
+            4 => Some([rest[0], rest[1], rest[2], rest[3]]),
+            _ => None,
+        };
+        if opcode.is_control() && (len > 125 || !fin) {
+            return Err(io::Error::new(
+                InvalidData,
+                "invalid websocket control frame",
+            ));
+        }
+        if mask.is_some() != (self.role == Role::Server) {
+            return Err(io::Error::new(
+                InvalidData,
+                "invalid websocket frame masking",
+            ));
+        }
+        if len > self.max_payload {
+            return Err(ProtocolError::error(1009, "websocket frame too large"));
+        }
+        Ok((Frame::new(fin, opcode, vec![0u8; len as usize]), mask))
+    }
+}
+
+impl<IO: AsyncRead + Unpin> IoFutureWithOutputState<IO, Frame> for FrameDecodeState {
+    fn poll(&mut self, cx: &mut Context<'_>, transport: &mut IO) -> Poll<io::Result<Frame>> {
+        loop {
+            let buf = match &mut self.frame {
+                Some((frame, mask)) => {
+                    if self.completion == frame.payload.len() {
+                        if let Some(mask) = *mask {
+                            apply_mask(&mut frame.payload, mask);
+                        }
+                        let (frame, _) = self.frame.take().unwrap();
+                        return Poll::Ready(Ok(frame));
+                    }
+                    &mut frame.payload[self.completion..]
+                }
+                None => {
+                    let expected = self.expected_head_len();
+                    if self.head_len >= 2 && self.head_len == expected {
+                        match self.parse_head() {
+                            Ok(frame) => self.frame = Some(frame),
+                            Err(err) => return Poll::Ready(Err(err)),
+                        }
+                        continue;
+                    }
+                    &mut self.head[self.head_len..expected]
+                }
+            };
+            match Pin::new(&mut *transport).poll_read(cx, buf) {
+                Poll::Ready(Ok(0)) => return Poll::Ready(Err(io::ErrorKind::UnexpectedEof.into())),
+                Poll::Ready(Ok(n)) => match self.frame.is_some() {
+                    true => self.completion += n,
+                    false => self.head_len += n,
+                },
+                Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+pub type FrameDecode<IO> = IoFutureWithOutput<FrameDecodeState, IO, Frame>;
+
+/// Random masking key for frames sent by clients, servers don't mask.
+pub(crate) fn mask_for(role: Role) -> Option<[u8; 4]> {
+    match role {
+        Role::Client => Some(rand::random()),
+        Role::Server => None,
+    }
+}
+
+fn apply_mask(payload: &mut [u8], mask: [u8; 4]) {
+    for (i, byte) in payload.iter_mut().enumerate() {
+        *byte ^= mask[i % 4];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::websocket::{Frame, Opcode, Role};
+    use futures::executor::block_on;
+    use futures::io::Cursor;
+
+    // examples from RFC 6455, section 5.7
+    const UNMASKED: &[u8] = &[0x81, 0x05, 0x48, 0x65, 0x6c, 0x6c, 0x6f];
+    const MASKED: &[u8] = &[
+        0x81, 0x85, 0x37, 0xfa, 0x21, 0x3d, 0x7f, 0x9f, 0x4d, 0x51, 0x58,
+    ];
+
+    #[test]
+    fn decode() {
+        block_on(async {
+            let hello = Frame::new(true, Opcode::Text, b"Hello".to_vec());
+            let (_, frame) = Frame::decode(Cursor::new(UNMASKED), Role::Client, 125)
+                .await
+                .unwrap();
+            assert_eq!(frame, hello);
+            let (_, frame) = Frame::decode(Cursor::new(MASKED), Role::Server, 125)
+                .await
+                .unwrap();
+            assert_eq!(frame, hello);
+            assert!(Frame::decode(Cursor::new(MASKED), Role::Client, 125)
+                .await
+                .is_err());
+            assert!(Frame::decode(Cursor::new(UNMASKED), Role::Client, 4)
+                .await
+                .is_err());
+        })
+    }
+
+    #[test]
+    fn encode() {
+        let hello = Frame::new(true, Opcode::Text, b"Hello".to_vec());
+        assert_eq!(hello.to_vec(None).unwrap(), UNMASKED);
+        assert_eq!(
+            hello.to_vec(Some([0x37, 0xfa, 0x21, 0x3d])).unwrap(),
+            MASKED
+        );
+        block_on(async {
+            let frame = Frame::new(false, Opcode::Binary, vec![7u8; 70000]);
+            let mut io = Cursor::new(Vec::new());
+            frame.encode(&mut io, Role::Client).await.unwrap();
+            io.set_position(0);
+            let (_, decoded) = Frame::decode(&mut io, Role::Server, 1 << 20).await.unwrap();
+            assert_eq!(decoded, frame);
+            let ping = Frame::new(true, Opcode::Ping, vec![0u8; 126]);
+            assert!(ping.to_vec(None).is_err());
+        })
+    }
+}
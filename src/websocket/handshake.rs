@@ -0,0 +1,108 @@
+use crate::{RequestHead, ResponseHead};
+use anyhow::bail;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use http::header::{
+    CONNECTION, SEC_WEBSOCKET_ACCEPT, SEC_WEBSOCKET_KEY, SEC_WEBSOCKET_VERSION, UPGRADE,
+};
+use http::{HeaderMap, HeaderValue, Method, StatusCode, Version};
+use sha1::{Digest, Sha1};
+use std::borrow::Cow;
+
+const GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// `Sec-WebSocket-Accept` value for a `Sec-WebSocket-Key`.
+pub fn accept_key(key: &str) -> String {
+    let mut hasher = Sha1::new();
+    hasher.update(key.as_bytes());
+    hasher.update(GUID.as_bytes());
+    STANDARD.encode(hasher.finalize())
+}
+
+/// Validates a websocket opening handshake and returns the `101 Switching Protocols` response
+/// head accepting it.
+pub fn server_handshake(request: &RequestHead) -> anyhow::Result<ResponseHead<'static>> {
+    if request.method() != Method::GET {
+        bail!("websocket handshake requires GET")
+    }
+    if !request
+        .upgrade_protocols()
+        .any(|p| p.eq_ignore_ascii_case("websocket"))
+    {
+        bail!("request does not offer an upgrade to websocket")
+    }
+    let headers = request.headers();
+    if headers.get(SEC_WEBSOCKET_VERSION).map(|v| v.as_bytes()) != Some(b"13") {
+        bail!("unsupported websocket version")
+    }
+    let key = match headers.get(SEC_WEBSOCKET_KEY).map(|v| v.to_str()) {
+        Some(Ok(key)) => key,
+        _ => bail!("missing websocket key"),
+    };
+    if !matches!(STANDARD.decode(key), Ok(nonce) if nonce.len() == 16) {
+        bail!("invalid websocket key")
+    }
+    let mut headers = HeaderMap::new();
+    headers.insert(UPGRADE, HeaderValue::from_static("websocket"));
+    headers.insert(CONNECTION, HeaderValue::from_static("upgrade"));
+    headers.insert(SEC_WEBSOCKET_ACCEPT, accept_key(key).parse()?);
+    Ok(ResponseHead::new(
+        StatusCode::SWITCHING_PROTOCOLS,
+        Version::HTTP_11,
+        Cow::Owned(headers),
+    ))
+}
+
+/// Turns `request` into a websocket opening handshake with a random key, which is returned for
+/// [`verify_response`].
+pub fn client_handshake(request: &mut RequestHead) -> String {
+    let key = STANDARD.encode(rand::random::<[u8; 16]>());
+    *request.method_mut() = Method::GET;
+    *request.version_mut() = Version::HTTP_11;
+    let headers = request.headers_mut();
+    headers.insert(UPGRADE, HeaderValue::from_static("websocket"));
+    headers.insert(CONNECTION, HeaderValue::from_static("upgrade"));
+    headers.insert(SEC_WEBSOCKET_VERSION, HeaderValue::from_static("13"));
+    headers.insert(SEC_WEBSOCKET_KEY, key.parse().unwrap());
+    key
+}
+
+/// Checks that `response` accepts the handshake made with `key`.
+pub fn verify_response(response: &ResponseHead, key: &str) -> anyhow::Result<()> {
+    if response.status() != StatusCode::SWITCHING_PROTOCOLS {
+        bail!("websocket handshake refused with {}", response.status())
+    }
+    let headers = response.headers();
+    if !matches!(headers.get(UPGRADE), Some(v) if v.as_bytes().eq_ignore_ascii_case(b"websocket"))
+        || !response.has_connection_token("upgrade")
+    {
+        bail!("response does not upgrade to websocket")
+    }
+    if headers.get(SEC_WEBSOCKET_ACCEPT).map(|v| v.as_bytes()) != Some(accept_key(key).as_bytes()) {
+        bail!("invalid websocket accept key")
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::websocket::{accept_key, client_handshake, server_handshake, verify_response};
+    use crate::RequestHead;
+    use http::Request;
+
+    #[test]
+    fn handshake() {
+        // example from RFC 6455, section 1.3
+        assert_eq!(
+            accept_key("dGhlIHNhbXBsZSBub25jZQ=="),
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+        let mut request = RequestHead::from(Request::get("/chat").body(()).unwrap());
+        assert!(server_handshake(&request).is_err());
+        let key = client_handshake(&mut request);
+        let response = server_handshake(&request).unwrap();
+        assert_eq!(response.status(), 101);
+        verify_response(&response, &key).unwrap();
+        assert!(verify_response(&response, "dGhlIHNhbXBsZSBub25jZQ==").is_err());
+    }
+}
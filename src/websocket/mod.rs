@@ -0,0 +1,7 @@
+mod frame;
+mod handshake;
+mod socket;
+
+pub use frame::*;
+pub use handshake::*;
+pub use socket::*;
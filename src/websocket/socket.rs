@@ -0,0 +1,506 @@
+use crate::client::{upgrade, UpgradeResponse};
+use crate::internal::io_future::IoFutureWithOutputState;
+use crate::websocket::frame::mask_for;
+use crate::websocket::{
+    client_handshake, server_handshake, verify_response, Frame, FrameDecodeState, Opcode, Role,
+};
+use crate::{RequestHead, ResponseHead};
+use anyhow::bail;
+use futures::prelude::*;
+use futures::ready;
+use std::fmt;
+use std::io;
+use std::io::ErrorKind::InvalidData;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Message {
+    Text(String),
+    Binary(Vec<u8>),
+}
+
+/// Status code and reason of a close frame.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CloseFrame {
+    pub code: u16,
+    pub reason: String,
+}
+
+impl CloseFrame {
+    pub fn new(code: u16, reason: impl Into<String>) -> Self {
+        Self {
+            code,
+            reason: reason.into(),
+        }
+    }
+    fn parse(payload: &[u8]) -> io::Result<Option<Self>> {
+        let code = match payload.len() {
+            0 => return Ok(None),
+            1 => return Err(io::Error::new(InvalidData, "invalid close frame")),
+            _ => u16::from_be_bytes([payload[0], payload[1]]),
+        };
+        // Codes reserved for use outside of close frames, or not assigned (RFC 6455, section 7.4).
+        if matches!(code, 0..=999 | 1004..=1006 | 1015..=2999 | 5000..) {
+            return Err(io::Error::new(InvalidData, "invalid close code"));
+        }
+        Ok(Some(Self {
+            code,
+            reason: String::from_utf8(payload[2..].to_vec())
+                .map_err(|_| ProtocolError::error(1007, "invalid close reason"))?,
+        }))
+    }
+    fn to_vec(&self) -> Vec<u8> {
+        let mut payload = self.code.to_be_bytes().to_vec();
+        payload.extend_from_slice(self.reason.as_bytes());
+        payload
+    }
+}
+
+/// Protocol violation by the peer, carried by [`io::ErrorKind::InvalidData`] errors along with
+/// the status code of the close frame it is answered with. Violations without this payload
+/// are answered with 1002 (protocol error).
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ProtocolError {
+    code: u16,
+    message: &'static str,
+}
+
+impl ProtocolError {
+    pub(crate) fn error(code: u16, message: &'static str) -> io::Error {
+        io::Error::new(InvalidData, Self { code, message })
+    }
+    /// Close code for an error returned while receiving.
+    pub fn close_code(err: &io::Error) -> Option<u16> {
+        match err.kind() {
+            InvalidData => Some(
+                err.get_ref()
+                    .and_then(|err| err.downcast_ref::<Self>())
+                    .map_or(1002, |err| err.code),
+            ),
+            _ => None,
+        }
+    }
+    pub fn code(&self) -> u16 {
+        self.code
+    }
+}
+
+impl fmt::Display for ProtocolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.message)
+    }
+}
+
+impl std::error::Error for ProtocolError {}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct WebSocketConfig {
+    /// Maximum size of a received message, after reassembling fragments.
+    pub max_message: usize,
+    /// Sent messages are fragmented into frames with at most this much payload.
+    pub max_frame: usize,
+}
+
+impl Default for WebSocketConfig {
+    fn default() -> Self {
+        Self {
+            max_message: 16 * 1024 * 1024,
+            max_frame: 64 * 1024,
+        }
+    }
+}
+
+/// Message-level websocket state, driven by the `poll_*` methods with the transport.
+///
+/// Pings are answered while receiving, fragmented messages are reassembled and close frames
+/// are answered to complete the close handshake. Partly received frames and messages are kept
+/// in the state, so a pending receive can be dropped without losing data.
+pub struct WebSocketState {
+    role: Role,
+    config: WebSocketConfig,
+    closing: bool,
+    peer_close: Option<Option<CloseFrame>>,
+    transport_closed: bool,
+    frame: Option<FrameDecodeState>,
+    message: Option<(Opcode, Vec<u8>)>,
+    output: Vec<u8>,
+    written: usize,
+    flushed: bool,
+    error: Option<io::Error>,
+}
+
+impl WebSocketState {
+    pub fn new(role: Role, config: WebSocketConfig) -> Self {
+        Self {
+            role,
+            config,
+            closing: false,
+            peer_close: None,
+            transport_closed: false,
+            frame: None,
+            message: None,
+            output: Vec::new(),
+            written: 0,
+            flushed: true,
+            error: None,
+        }
+    }
+    pub fn into_async<IO: AsyncRead + AsyncWrite + Unpin>(self, io: IO) -> WebSocket<IO> {
+        WebSocket { io, state: self }
+    }
+    /// Close frame received from the peer, once the connection was closed by it.
+    pub fn close_frame(&self) -> Option<&CloseFrame> {
+        self.peer_close.as_ref().and_then(Option::as_ref)
+    }
+    /// Whether a close frame was sent, after which no more messages can be sent.
+    pub fn is_closing(&self) -> bool {
+        self.closing
+    }
+    /// Queues `message`, fragmented into frames of at most [`WebSocketConfig::max_frame`]
+    /// bytes. Queued frames are written by [`poll_flush`](Self::poll_flush).
+    pub fn start_send(&mut self, message: &Message) -> io::Result<()> {
+        if self.closing {
+            return Err(io::Error::new(
+                io::ErrorKind::NotConnected,
+                "websocket is closing",
+            ));
+        }
+        let (mut opcode, payload) = match message {
+            Message::Text(text) => (Opcode::Text, text.as_bytes()),
+            Message::Binary(data) => (Opcode::Binary, data.as_slice()),
+        };
+        let mut chunks = payload.chunks(self.config.max_frame.max(1)).peekable();
+        loop {
+            let chunk = chunks.next().unwrap_or_default();
+            let fin = chunks.peek().is_none();
+            self.queue(Frame::new(fin, opcode, chunk.to_vec()))?;
+            opcode = Opcode::Continuation;
+            if fin {
+                return Ok(());
+            }
+        }
+    }
+    /// Queues a ping frame.
+    pub fn start_ping(&mut self, payload: Vec<u8>) -> io::Result<()> {
+        self.queue(Frame::new(true, Opcode::Ping, payload))
+    }
+    /// Queues a close frame to start the close handshake, unless one was sent already.
+    pub fn start_close(&mut self, frame: Option<CloseFrame>) -> io::Result<()> {
+        if !self.closing {
+            let payload = frame.as_ref().map(CloseFrame::to_vec).unwrap_or_default();
+            self.queue(Frame::new(true, Opcode::Close, payload))?;
+            self.closing = true;
+        }
+        Ok(())
+    }
+    /// Writes the queued frames and flushes the transport.
+    pub fn poll_flush<IO: AsyncWrite + Unpin>(
+        &mut self,
+        cx: &mut Context<'_>,
+        io: &mut IO,
+    ) -> Poll<io::Result<()>> {
+        while self.written < self.output.len() {
+            match ready!(Pin::new(&mut *io).poll_write(cx, &self.output[self.written..]))? {
+                0 => return Poll::Ready(Err(io::ErrorKind::WriteZero.into())),
+                n => self.written += n,
+            }
+        }
+        self.output.clear();
+        self.written = 0;
+        if !self.flushed {
+            ready!(Pin::new(&mut *io).poll_flush(cx))?;
+            self.flushed = true;
+        }
+        Poll::Ready(Ok(()))
+    }
+    /// Receives the next message. Returns `None` once the peer closed the websocket.
+    ///
+    /// Protocol violations by the peer are answered with a close frame before failing, see
+    /// [`ProtocolError`].
+    pub fn poll_receive<IO: AsyncRead + AsyncWrite + Unpin>(
+        &mut self,
+        cx: &mut Context<'_>,
+        io: &mut IO,
+    ) -> Poll<io::Result<Option<Message>>> {
+        loop {
+            if self.error.is_some() {
+                // The close frame answering the error is sent on a best effort basis.
+                ready!(self.poll_flush(cx, io)).ok();
+                return Poll::Ready(Err(self.error.take().unwrap()));
+            }
+            ready!(self.poll_flush(cx, io))?;
+            if self.peer_close.is_some() {
+                if !self.transport_closed {
+                    ready!(Pin::new(&mut *io).poll_close(cx))?;
+                    self.transport_closed = true;
+                }
+                return Poll::Ready(Ok(None));
+            }
+            let received = self.message.as_ref().map_or(0, |(_, data)| data.len());
+            let limit = self.config.max_message.saturating_sub(received) as u64;
+            let role = self.role;
+            let frame = self
+                .frame
+                .get_or_insert_with(|| Frame::decode_state(role, limit));
+            let result = ready!(frame.poll(cx, io));
+            self.frame = None;
+            match result.and_then(|frame| self.handle_frame(frame)) {
+                Ok(Some(message)) => return Poll::Ready(Ok(Some(message))),
+                Ok(None) => {}
+                Err(err) if err.kind() == InvalidData && !self.closing => {
+                    let code = ProtocolError::close_code(&err).unwrap_or(1002);
+                    self.message = None;
+                    self.start_close(Some(CloseFrame::new(code, "")))?;
+                    self.error = Some(err);
+                }
+                Err(err) => return Poll::Ready(Err(err)),
+            }
+        }
+    }
+    fn handle_frame(&mut self, frame: Frame) -> io::Result<Option<Message>> {
+        match (frame.opcode, &mut self.message) {
+            (Opcode::Ping, _) if !self.closing => {
+                self.queue(Frame::new(true, Opcode::Pong, frame.payload))?;
+                return Ok(None);
+            }
+            (Opcode::Ping | Opcode::Pong, _) => return Ok(None),
+            (Opcode::Close, _) => {
+                let close = CloseFrame::parse(&frame.payload)?;
+                if !self.closing {
+                    let payload = frame.payload.get(..2).unwrap_or_default().to_vec();
+                    self.queue(Frame::new(true, Opcode::Close, payload))?;
+                    self.closing = true;
+                }
+                self.peer_close = Some(close);
+                return Ok(None);
+            }
+            (Opcode::Continuation, Some((_, data))) => data.extend(frame.payload),
+            (Opcode::Text | Opcode::Binary, None) => {
+                self.message = Some((frame.opcode, frame.payload))
+            }
+            _ => return Err(io::Error::new(InvalidData, "unexpected websocket frame")),
+        }
+        if !frame.fin {
+            return Ok(None);
+        }
+        match self.message.take().unwrap() {
+            (Opcode::Text, data) => match String::from_utf8(data) {
+                Ok(text) => Ok(Some(Message::Text(text))),
+                Err(_) => Err(ProtocolError::error(1007, "invalid utf-8 in text message")),
+            },
+            (_, data) => Ok(Some(Message::Binary(data))),
+        }
+    }
+    fn queue(&mut self, frame: Frame) -> io::Result<()> {
+        self.output.extend(frame.to_vec(mask_for(self.role))?);
+        self.flushed = false;
+        Ok(())
+    }
+}
+
+/// Message-level websocket over an upgraded transport, see [`WebSocketState`].
+pub struct WebSocket<IO: AsyncRead + AsyncWrite + Unpin> {
+    io: IO,
+    state: WebSocketState,
+}
+
+impl<IO: AsyncRead + AsyncWrite + Unpin> WebSocket<IO> {
+    pub fn new(io: IO, role: Role) -> Self {
+        Self::with_config(io, role, WebSocketConfig::default())
+    }
+    pub fn with_config(io: IO, role: Role, config: WebSocketConfig) -> Self {
+        WebSocketState::new(role, config).into_async(io)
+    }
+    /// Completes the server handshake for `request` and returns the websocket.
+    pub async fn accept(mut io: IO, request: &RequestHead<'_>) -> anyhow::Result<Self> {
+        server_handshake(request)?.encode(&mut io).await?;
+        io.flush().await?;
+        Ok(Self::new(io, Role::Server))
+    }
+    /// Performs the client handshake with `request` and returns the websocket.
+    pub async fn connect(
+        io: IO,
+        mut request: RequestHead<'_>,
+    ) -> anyhow::Result<(ResponseHead<'static>, Self)> {
+        let key = client_handshake(&mut request);
+        match upgrade(io, request, "websocket", futures::io::empty()).await? {
+            UpgradeResponse::Switched(response, io) => {
+                verify_response(&response, &key)?;
+                Ok((response, Self::new(io, Role::Client)))
+            }
+            UpgradeResponse::Refused(response, _) => {
+                bail!("websocket handshake refused with {}", response.status())
+            }
+        }
+    }
+    pub fn into_inner(self) -> IO {
+        self.io
+    }
+    pub fn into_parts(self) -> (WebSocketState, IO) {
+        (self.state, self.io)
+    }
+    pub fn state(&self) -> &WebSocketState {
+        &self.state
+    }
+    /// Close frame received from the peer, once the connection was closed by it.
+    pub fn close_frame(&self) -> Option<&CloseFrame> {
+        self.state.close_frame()
+    }
+    pub fn poll_receive(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<Option<Message>>> {
+        self.state.poll_receive(cx, &mut self.io)
+    }
+    pub fn poll_flush(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.state.poll_flush(cx, &mut self.io)
+    }
+    pub async fn send(&mut self, message: &Message) -> io::Result<()> {
+        self.state.start_send(message)?;
+        future::poll_fn(|cx| self.poll_flush(cx)).await
+    }
+    pub async fn ping(&mut self, payload: Vec<u8>) -> io::Result<()> {
+        self.state.start_ping(payload)?;
+        future::poll_fn(|cx| self.poll_flush(cx)).await
+    }
+    /// Receives the next message, see [`WebSocketState::poll_receive`]. Dropping the returned
+    /// future doesn't lose received data.
+    pub async fn receive(&mut self) -> io::Result<Option<Message>> {
+        future::poll_fn(|cx| self.poll_receive(cx)).await
+    }
+    /// Starts the close handshake and discards messages until the peer answers it.
+    pub async fn close(&mut self, frame: Option<CloseFrame>) -> io::Result<()> {
+        self.state.start_close(frame)?;
+        while self.receive().await?.is_some() {}
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::internal::mock_io::MockIo;
+    use crate::websocket::{
+        CloseFrame, Frame, Message, Opcode, ProtocolError, Role, WebSocket, WebSocketConfig,
+    };
+    use futures::executor::block_on;
+    use futures::io::Cursor;
+    use futures::task::noop_waker_ref;
+    use std::future::Future;
+    use std::pin::pin;
+    use std::task::Context;
+
+    fn client_frames(frames: &[Frame]) -> Vec<u8> {
+        frames
+            .iter()
+            .flat_map(|frame| frame.to_vec(Some(rand::random())).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn receive_and_close() {
+        block_on(async {
+            let mut io = MockIo::new(&client_frames(&[
+                Frame::new(false, Opcode::Text, b"Hello".to_vec()),
+                Frame::new(true, Opcode::Ping, b"ping".to_vec()),
+                Frame::new(true, Opcode::Continuation, b" world".to_vec()),
+                Frame::new(true, Opcode::Close, CloseFrame::new(1000, "bye").to_vec()),
+            ]));
+            let mut socket = WebSocket::new(&mut io, Role::Server);
+            let message = socket.receive().await.unwrap();
+            assert_eq!(message, Some(Message::Text("Hello world".to_string())));
+            assert_eq!(socket.receive().await.unwrap(), None);
+            assert_eq!(socket.close_frame(), Some(&CloseFrame::new(1000, "bye")));
+            assert!(socket.send(&Message::Binary(vec![])).await.is_err());
+            assert!(io.closed);
+
+            let mut output = Cursor::new(io.output);
+            for expected in [
+                Frame::new(true, Opcode::Pong, b"ping".to_vec()),
+                Frame::new(true, Opcode::Close, vec![0x03, 0xe8]),
+            ] {
+                let (_, frame) = Frame::decode(&mut output, Role::Client, 125).await.unwrap();
+                assert_eq!(frame, expected);
+            }
+        })
+    }
+
+    #[test]
+    fn fragmented_round_trip() {
+        block_on(async {
+            let config = WebSocketConfig {
+                max_frame: 4,
+                ..WebSocketConfig::default()
+            };
+            let message = Message::Binary(b"a fragmented message".to_vec());
+            let mut client = WebSocket::with_config(MockIo::new(b""), Role::Client, config);
+            client.send(&message).await.unwrap();
+            client.close(None).await.ok();
+            let output = client.into_inner().output;
+
+            let mut server = WebSocket::new(MockIo::new(&output), Role::Server);
+            assert_eq!(server.receive().await.unwrap(), Some(message));
+            assert_eq!(server.receive().await.unwrap(), None);
+            assert_eq!(server.close_frame(), None);
+
+            let mut server = WebSocket::with_config(
+                MockIo::new(&output),
+                Role::Server,
+                WebSocketConfig {
+                    max_message: 8,
+                    ..WebSocketConfig::default()
+                },
+            );
+            let err = server.receive().await.unwrap_err();
+            assert_eq!(ProtocolError::close_code(&err), Some(1009));
+            let output = server.into_inner().output;
+            let (_, frame) = Frame::decode(Cursor::new(output), Role::Client, 125)
+                .await
+                .unwrap();
+            assert_eq!(frame.payload, [0x03, 0xf1]);
+        })
+    }
+
+    #[test]
+    fn receive_cancelled() {
+        let input = client_frames(&[
+            Frame::new(false, Opcode::Binary, b"first ".to_vec()),
+            Frame::new(true, Opcode::Continuation, b"second".to_vec()),
+        ]);
+        let mut io = MockIo::new(&input[..15]);
+        io.hold_eof = true;
+        let mut socket = WebSocket::new(&mut io, Role::Server);
+        let mut cx = Context::from_waker(noop_waker_ref());
+        {
+            let receive = pin!(socket.receive());
+            assert!(receive.poll(&mut cx).is_pending());
+        }
+        socket.io.input.get_mut().extend_from_slice(&input[15..]);
+        let message = block_on(socket.receive()).unwrap();
+        assert_eq!(message, Some(Message::Binary(b"first second".to_vec())));
+    }
+
+    async fn receive_error(frame: Frame) -> (u16, Vec<u8>) {
+        let mut server = WebSocket::new(MockIo::new(&client_frames(&[frame])), Role::Server);
+        let err = server.receive().await.unwrap_err();
+        let output = server.into_inner().output;
+        let (_, frame) = Frame::decode(Cursor::new(output), Role::Client, 125)
+            .await
+            .unwrap();
+        assert_eq!(frame.opcode, Opcode::Close);
+        (ProtocolError::close_code(&err).unwrap(), frame.payload)
+    }
+
+    #[test]
+    fn close_codes() {
+        block_on(async {
+            let frame = Frame::new(true, Opcode::Text, vec![0xff, 0xfe]);
+            assert_eq!(receive_error(frame).await, (1007, vec![0x03, 0xef]));
+            for code in [999u16, 1005, 1015, 2999, 5000] {
+                let frame = Frame::new(true, Opcode::Close, code.to_be_bytes().to_vec());
+                assert_eq!(receive_error(frame).await, (1002, vec![0x03, 0xea]));
+            }
+            let mut payload = 1000u16.to_be_bytes().to_vec();
+            payload.push(0xff);
+            let frame = Frame::new(true, Opcode::Close, payload);
+            assert_eq!(receive_error(frame).await, (1007, vec![0x03, 0xef]));
+        })
+    }
+}